@@ -0,0 +1,193 @@
+// Latency of the solver's hot paths on mid-game boards. Each call is timed on
+// its own, so besides criterion's report the p50 and p95 of its samples are
+// printed, since a few large boundaries dominate the mean. Run with
+// `cargo bench`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use minesweeper::agent::{
+    self, deductions, guesses, ActionOrigin, BotSkill, FirstGuess, GuessBudget,
+    GuessStyle, SolverCache,
+};
+use minesweeper::board::{Board, TilePos};
+use minesweeper::Difficulty;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const SEEDS: u64 = 40;
+const BOARDS: usize = 8;
+
+fn boundary_size(board: &Board) -> usize {
+    board
+        .tiles()
+        .filter(|&(pos, state)| {
+            state.covered()
+                && !agent::uncovered_neighbours(board, pos).is_empty()
+        })
+        .count()
+}
+
+/// The board with the most boundary tiles the bot had to guess on in each
+/// Hard game, the largest first
+fn guess_boards() -> Vec<Board> {
+    let mut boards = vec![];
+    for seed in 0..SEEDS {
+        // neighbouring seeds often reroll to the same first click layout
+        let seed = StdRng::seed_from_u64(seed).gen();
+        let mut board = Board::new(Difficulty::Hard, Some(seed));
+        let mut actions = vec![];
+        agent::solve_cached(
+            &mut board.clone(),
+            &mut SolverCache::default(),
+            FirstGuess::default(),
+            GuessStyle::default(),
+            GuessBudget::default(),
+            BotSkill::default(),
+            |action, origin| actions.push((action, origin)),
+        );
+        // play the game again to see the board before each guess
+        let mut largest: Option<Board> = None;
+        for (action, origin) in actions {
+            // the first click isn't much of a guess
+            if origin == ActionOrigin::Guessed
+                && board.first_uncovered()
+                && largest.as_ref().is_none_or(|largest| {
+                    boundary_size(&board) > boundary_size(largest)
+                })
+            {
+                largest = Some(board.clone());
+            }
+            board.apply_action(action);
+        }
+        boards.extend(largest);
+    }
+    boards.sort_by_key(|board| std::cmp::Reverse(boundary_size(board)));
+    boards.truncate(BOARDS);
+    boards
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+// goes round the boards one call at a time, keeping every call's time
+fn bench(c: &mut Criterion, name: &str, boards: &[Board], f: impl Fn(&Board)) {
+    let mut times = vec![];
+    c.bench_function(name, |b| {
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for i in 0..iters as usize {
+                let start = Instant::now();
+                f(black_box(&boards[i % boards.len()]));
+                let time = start.elapsed();
+                times.push(time);
+                total += time;
+            }
+            total
+        })
+    });
+    // nothing was timed if the bench was filtered out
+    if times.is_empty() {
+        return;
+    }
+    times.sort();
+    println!(
+        "{name}: p50 {:.3?}, p95 {:.3?} over {} calls",
+        percentile(&times, 0.5),
+        percentile(&times, 0.95),
+        times.len()
+    );
+}
+
+fn solver(c: &mut Criterion) {
+    let boards = guess_boards();
+    let sizes = boards.iter().map(boundary_size).collect::<Vec<_>>();
+    println!("{} boards, boundaries of {sizes:?} tiles", boards.len());
+    bench(c, "make_guess", &boards, |board| {
+        guesses::make_guess(board, GuessBudget::default(), 0.0);
+    });
+    bench(c, "get_non_trivial_actions", &boards, |board| {
+        deductions::get_non_trivial_actions(board);
+    });
+}
+
+/// The boards the bot made trivial deductions on in a few Hard games, in
+/// the order they came up
+fn trivial_boards() -> Vec<Board> {
+    let mut boards = vec![];
+    for seed in 0..5 {
+        let mut board = Board::new(Difficulty::Hard, Some(seed));
+        let mut actions = vec![];
+        agent::solve_cached(
+            &mut board.clone(),
+            &mut SolverCache::default(),
+            FirstGuess::default(),
+            GuessStyle::default(),
+            GuessBudget::default(),
+            BotSkill::default(),
+            |action, origin| actions.push((action, origin)),
+        );
+        for (action, origin) in actions {
+            if origin == ActionOrigin::Trivial && board.first_uncovered() {
+                boards.push(board.clone());
+            }
+            board.apply_action(action);
+        }
+    }
+    boards
+}
+
+// a full scan of every number against the cache, which only redoes numbers
+// next to tiles changed since the previous board
+fn trivial_deduction(c: &mut Criterion) {
+    let boards = trivial_boards();
+    c.bench_function("trivial deduction, full scan", |b| {
+        b.iter(|| {
+            for board in &boards {
+                agent::get_trivial_actions(black_box(board));
+            }
+        })
+    });
+    c.bench_function("trivial deduction, cached", |b| {
+        b.iter(|| {
+            let mut cache = SolverCache::default();
+            for board in &boards {
+                agent::get_all_actions_cached(
+                    black_box(board),
+                    &mut cache,
+                    FirstGuess::default(),
+                    GuessStyle::default(),
+                    GuessBudget::default(),
+                    BotSkill::default(),
+                );
+            }
+        })
+    });
+}
+
+// the bitset against the `Vec<bool>` the bombs used to be kept in, on a
+// 100x100 board a fifth bombs
+fn bomb_positions(c: &mut Criterion) {
+    let board = Board::with_dimensions(100, 100, 2000, Some(0)).unwrap();
+    let mut bools = vec![false; 100 * 100];
+    for pos in board.bomb_positions() {
+        bools[pos.row * 100 + pos.col] = true;
+    }
+    c.bench_function("bomb_positions", |b| {
+        b.iter(|| black_box(&board).bomb_positions())
+    });
+    c.bench_function("bomb_positions from Vec<bool>", |b| {
+        b.iter(|| {
+            black_box(&bools)
+                .iter()
+                .enumerate()
+                .filter(|(_, &bomb)| bomb)
+                .map(|(index, _)| TilePos {
+                    col: index % 100,
+                    row: index / 100,
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, solver, trivial_deduction, bomb_positions);
+criterion_main!(benches);
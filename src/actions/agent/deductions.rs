@@ -1,7 +1,39 @@
 use super::*;
 use crate::TilePos;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+type Bounds = (HashMap<Vec<TilePos>, u8>, HashMap<Vec<TilePos>, u8>);
+
+/// An uncovered number with the bombs still to find around it and the
+/// covered tiles they could be in
+type Constraint = (TilePos, u8, Vec<TilePos>);
+
+/// Subset bounds for each independent group of constraints. A group that
+/// shares no covered tiles with the others can't affect their bounds, so its
+/// bounds stay valid until one of its own tiles changes.
+#[derive(Default)]
+pub struct SolverCache {
+    bounds: HashMap<Vec<Constraint>, Bounds>,
+    // tiles at the last scan, and the trivial actions each number gave
+    pub(super) tiles: Vec<TileState>,
+    pub(super) trivial: BTreeMap<TilePos, Vec<Action>>,
+    pub(super) timings: SolverTimings,
+    pub(super) guess_odds: Vec<Option<f64>>,
+}
+
+impl SolverCache {
+    /// Time spent in each stage of `get_all_actions_cached` so far
+    pub fn timings(&self) -> SolverTimings {
+        self.timings
+    }
+
+    /// Odds that each guess chosen so far was safe, in order, as worked out
+    /// when choosing it. `None` for rough guesses and slips.
+    pub fn guess_odds(&self) -> &[Option<f64>] {
+        &self.guess_odds
+    }
+}
 
 fn subsets(elts: &[TilePos], max_size: usize) -> Vec<Vec<&TilePos>> {
     (2..=max_size)
@@ -90,67 +122,122 @@ fn min_in_subset(
     biggest_min
 }
 
-pub fn get_subset_bounds(
+pub fn get_subset_bounds(board: &Board) -> Bounds {
+    subset_bounds(&constraints(board))
+}
+
+/// Same as `get_subset_bounds`, only recomputing groups of constraints that
+/// have changed since the last call
+pub fn get_subset_bounds_cached(
     board: &Board,
-) -> (HashMap<Vec<TilePos>, u8>, HashMap<Vec<TilePos>, u8>) {
+    cache: &mut SolverCache,
+) -> Bounds {
+    let mut min_bombs = HashMap::new();
+    let mut max_bombs = HashMap::new();
+    let mut used = HashMap::new();
+    for group in constraint_groups(constraints(board)) {
+        let bounds = cache
+            .bounds
+            .remove(&group)
+            .unwrap_or_else(|| subset_bounds(&group));
+        min_bombs.extend(bounds.0.iter().map(|(k, &v)| (k.clone(), v)));
+        max_bombs.extend(bounds.1.iter().map(|(k, &v)| (k.clone(), v)));
+        used.insert(group, bounds);
+    }
+    // drop groups that no longer exist on the board
+    cache.bounds = used;
+    (min_bombs, max_bombs)
+}
+
+// in column major order, like the rest of the solver
+fn constraints(board: &Board) -> Vec<Constraint> {
+    let mut output = vec![];
+    for col in 0..board.width() {
+        for row in 0..board.height() {
+            let pos = TilePos { col, row };
+            if let TileState::UncoveredSafe(n) = board.tile_state(pos) {
+                let covered = covered_neighbours(board, pos);
+                if !covered.is_empty() {
+                    let n = n - num_bombs_around(board, pos);
+                    output.push((pos, n, covered));
+                }
+            }
+        }
+    }
+    output
+}
+
+// split into groups which share no covered tiles
+fn constraint_groups(constraints: Vec<Constraint>) -> Vec<Vec<Constraint>> {
+    let mut groups: Vec<Vec<Constraint>> = vec![];
+    for constraint in constraints {
+        let (overlapping, mut rest): (Vec<_>, Vec<_>) =
+            groups.into_iter().partition(|group| {
+                group.iter().any(|(_, _, covered)| {
+                    covered.iter().any(|pos| constraint.2.contains(pos))
+                })
+            });
+        let mut group = overlapping.concat();
+        group.push(constraint);
+        group.sort_by_key(|&(pos, _, _)| pos);
+        rest.push(group);
+        groups = rest;
+    }
+    groups
+}
+
+fn subset_bounds(constraints: &[Constraint]) -> Bounds {
     let mut min_bombs: HashMap<Vec<TilePos>, u8> = HashMap::new();
     let mut max_bombs: HashMap<Vec<TilePos>, u8> = HashMap::new();
     for _ in 0..3 {
-        update_subset_bounds(board, &mut min_bombs, &mut max_bombs);
+        update_subset_bounds(constraints, &mut min_bombs, &mut max_bombs);
     }
     (min_bombs, max_bombs)
 }
 
 fn update_subset_bounds(
-    board: &Board,
+    constraints: &[Constraint],
     min_bombs: &mut HashMap<Vec<TilePos>, u8>,
     max_bombs: &mut HashMap<Vec<TilePos>, u8>,
 ) {
-    for col in 0..board.width() {
-        for row in 0..board.height() {
-            let pos = TilePos { col, row };
-            if let TileState::UncoveredSafe(n) = board.tile_state(pos) {
-                let n = n - num_bombs_around(board, pos);
-                let covered = covered_neighbours(board, pos);
-                let num_covered = covered.len();
-                for subset in subsets(&covered, num_covered) {
-                    let subset = subset.iter().cloned().cloned().collect_vec();
-                    // rule 1: at most n bombs in all subsets around the tile
-                    if subset.len() > n as usize {
-                        if let Some(max) = max_bombs.get(&subset) {
-                            if n < *max {
-                                max_bombs.insert(subset.clone(), n);
-                            }
-                        } else {
-                            max_bombs.insert(subset.clone(), n);
-                        }
+    for (_, n, covered) in constraints {
+        let n = *n;
+        let num_covered = covered.len();
+        for subset in subsets(covered, num_covered) {
+            let subset = subset.iter().cloned().cloned().collect_vec();
+            // rule 1: at most n bombs in all subsets around the tile
+            if subset.len() > n as usize {
+                if let Some(max) = max_bombs.get(&subset) {
+                    if n < *max {
+                        max_bombs.insert(subset.clone(), n);
                     }
-                    // rule 2: if we exclude tiles with a max of k bombs there
-                    // are at least n - k bombs in the remaining subset
-                    let rest = set_difference(&covered.clone(), &subset);
-                    let max_omitted = max_in_subset(&rest, max_bombs);
-                    if n > max_omitted {
-                        if let Some(min) = min_bombs.get(&subset) {
-                            if n - max_omitted > *min {
-                                min_bombs
-                                    .insert(subset.clone(), n - max_omitted);
-                            }
-                        } else {
-                            min_bombs.insert(subset.clone(), n - max_omitted);
-                        }
+                } else {
+                    max_bombs.insert(subset.clone(), n);
+                }
+            }
+            // rule 2: if we exclude tiles with a max of k bombs there
+            // are at least n - k bombs in the remaining subset
+            let rest = set_difference(&covered.clone(), &subset);
+            let max_omitted = max_in_subset(&rest, max_bombs);
+            if n > max_omitted {
+                if let Some(min) = min_bombs.get(&subset) {
+                    if n - max_omitted > *min {
+                        min_bombs.insert(subset.clone(), n - max_omitted);
                     }
-                    // rule 3: if we exclude tiles with a min of k bombs there
-                    // are at most n - k bombs in the remaining subset
-                    let min_omitted = min_in_subset(&rest, min_bombs);
-                    if n > min_omitted {
-                        if let Some(max) = max_bombs.get(&subset) {
-                            if n - min_omitted < *max {
-                                max_bombs.insert(subset, n - min_omitted);
-                            }
-                        } else {
-                            max_bombs.insert(subset, n - min_omitted);
-                        }
+                } else {
+                    min_bombs.insert(subset.clone(), n - max_omitted);
+                }
+            }
+            // rule 3: if we exclude tiles with a min of k bombs there
+            // are at most n - k bombs in the remaining subset
+            let min_omitted = min_in_subset(&rest, min_bombs);
+            if n > min_omitted {
+                if let Some(max) = max_bombs.get(&subset) {
+                    if n - min_omitted < *max {
+                        max_bombs.insert(subset, n - min_omitted);
                     }
+                } else {
+                    max_bombs.insert(subset, n - min_omitted);
                 }
             }
         }
@@ -158,8 +245,40 @@ fn update_subset_bounds(
 }
 
 pub fn get_non_trivial_actions(board: &Board) -> Vec<Action> {
+    without_reasons(actions_from_bounds(board, get_subset_bounds(board), false))
+}
+
+pub fn get_non_trivial_actions_cached(
+    board: &Board,
+    cache: &mut SolverCache,
+) -> Vec<Action> {
+    let bounds = get_subset_bounds_cached(board, cache);
+    without_reasons(actions_from_bounds(board, bounds, false))
+}
+
+/// Same as `get_non_trivial_actions`, with the reasoning behind each action,
+/// e.g. "(4, 5) needs 2 bombs; {(3, 4), (3, 6)} can hold at most 0, so
+/// {(5, 4), (5, 6)} must be bombs"
+pub fn get_non_trivial_actions_explained(
+    board: &Board,
+) -> Vec<(Action, String)> {
+    actions_from_bounds(board, get_subset_bounds(board), true)
+        .into_iter()
+        .map(|(action, reason)| (action, reason.unwrap_or_default()))
+        .collect()
+}
+
+fn without_reasons(actions: Vec<(Action, Option<String>)>) -> Vec<Action> {
+    actions.into_iter().map(|(action, _)| action).collect()
+}
+
+// reasons are only written out when asked for, to keep solving fast
+fn actions_from_bounds(
+    board: &Board,
+    (mut min_bombs, mut max_bombs): Bounds,
+    explain: bool,
+) -> Vec<(Action, Option<String>)> {
     let mut output = vec![];
-    let (mut min_bombs, mut max_bombs) = get_subset_bounds(board);
     // check each uncovered tile to see if we have helpful adjacent subsets
     (0..board.width())
         .cartesian_product(0..board.height())
@@ -179,6 +298,7 @@ pub fn get_non_trivial_actions(board: &Board) -> Vec<Action> {
                 return;
             }
             for subset in subsets(&covered, num_covered - 1) {
+                let rest = covered.iter().filter(|x| !subset.contains(x));
                 // need so few bombs in subset that the rest must be bombs
                 let max = max_in_subset(
                     &subset.iter().cloned().cloned().collect(),
@@ -186,11 +306,18 @@ pub fn get_non_trivial_actions(board: &Board) -> Vec<Action> {
                 );
                 let rest_size = (num_covered - subset.len()) as u8;
                 if max + rest_size == n {
-                    covered
-                        .iter()
-                        .filter(|x| !subset.contains(x))
-                        .map(|&pos| Action::flag(pos))
-                        .for_each(|x| output.push(x));
+                    let reason = explain.then(|| {
+                        format!(
+                            "{} needs {n} bombs; {} can hold at most {max}, \
+                             so {} must be bombs",
+                            format_tile(&pos),
+                            format_tiles(&subset),
+                            format_tiles(&rest.clone().collect_vec()),
+                        )
+                    });
+                    for &pos in rest.clone() {
+                        push_unique(&mut output, Action::flag(pos), &reason);
+                    }
                 }
 
                 // need at least n bombs in the subset, then rest are safe
@@ -199,13 +326,102 @@ pub fn get_non_trivial_actions(board: &Board) -> Vec<Action> {
                     &mut min_bombs,
                 );
                 if min == n {
-                    covered
-                        .iter()
-                        .filter(|x| !subset.contains(x))
-                        .map(|&pos| Action::uncover(pos))
-                        .for_each(|x| output.push(x));
+                    let reason = explain.then(|| {
+                        format!(
+                            "{} needs {n} bombs; {} must hold at least {min}, \
+                             so {} must be safe",
+                            format_tile(&pos),
+                            format_tiles(&subset),
+                            format_tiles(&rest.clone().collect_vec()),
+                        )
+                    });
+                    for &pos in rest {
+                        push_unique(&mut output, Action::uncover(pos), &reason);
+                    }
                 }
             }
         });
-    deduplicate(output)
+    for (action, reason) in global_actions(board, &min_bombs, explain) {
+        push_unique(&mut output, action, &reason);
+    }
+    output
+}
+
+/// Tiles away from the boundary are safe once the boundary needs every bomb
+/// left. Its minimum comes from disjoint groups of tiles with known minimums,
+/// packed greedily.
+fn global_actions(
+    board: &Board,
+    min_bombs: &HashMap<Vec<TilePos>, u8>,
+    explain: bool,
+) -> Vec<(Action, Option<String>)> {
+    let (boundary, non_boundary): (Vec<_>, Vec<_>) = board
+        .tiles()
+        .filter(|(_, state)| state.covered())
+        .map(|(pos, _)| pos)
+        .partition(|&pos| !uncovered_neighbours(board, pos).is_empty());
+    let num_bombs_left = board.num_bombs_left();
+    // the packing can't hold more bombs than there are boundary tiles
+    if non_boundary.is_empty()
+        || num_bombs_left < 0
+        || num_bombs_left as usize > boundary.len()
+    {
+        return vec![];
+    }
+    let groups = constraints(board)
+        .into_iter()
+        .map(|(_, n, covered)| (covered, n))
+        .chain(min_bombs.iter().map(|(tiles, &min)| (tiles.clone(), min)))
+        .filter(|&(_, min)| min > 0)
+        // sorted in full so the packing doesn't depend on hash order
+        .sorted_by(|(tiles1, min1), (tiles2, min2)| {
+            min2.cmp(min1)
+                .then(tiles1.len().cmp(&tiles2.len()))
+                .then(tiles1.cmp(tiles2))
+        });
+    let mut packed: Vec<TilePos> = vec![];
+    let mut boundary_min = 0;
+    for (tiles, min) in groups {
+        if tiles.iter().all(|pos| !packed.contains(pos)) {
+            packed.extend(tiles);
+            boundary_min += min as isize;
+        }
+    }
+    if boundary_min != num_bombs_left {
+        return vec![];
+    }
+    let reason = explain.then(|| {
+        format!(
+            "{} must hold at least {boundary_min} bombs, all that are left, \
+             so {} must be safe",
+            format_tiles(&packed.iter().collect_vec()),
+            format_tiles(&non_boundary.iter().collect_vec()),
+        )
+    });
+    non_boundary
+        .into_iter()
+        .map(|pos| (Action::uncover(pos), reason.clone()))
+        .collect()
+}
+
+// the first reason found for an action is the one kept
+fn push_unique(
+    output: &mut Vec<(Action, Option<String>)>,
+    action: Action,
+    reason: &Option<String>,
+) {
+    if !output.iter().any(|(x, _)| *x == action) {
+        output.push((action, reason.clone()));
+    }
+}
+
+fn format_tile(&TilePos { col, row }: &TilePos) -> String {
+    format!("({col}, {row})")
+}
+
+fn format_tiles(tiles: &[&TilePos]) -> String {
+    format!(
+        "{{{}}}",
+        tiles.iter().map(|pos| format_tile(pos)).join(", ")
+    )
 }
@@ -1,7 +1,12 @@
 use super::*;
 use crate::TilePos;
+use bevy::log::debug;
 use instant::Instant;
-use itertools::{Itertools, MinMaxResult};
+use itertools::Itertools;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use std::collections::HashMap;
 
 fn case_weight(
     num_bombs_omitted: usize,
@@ -35,6 +40,32 @@ fn case_weight(
     output
 }
 
+// checking the clock is slow next to validating a scenario, so it's only
+// looked at every so often
+struct Deadline {
+    at: Option<Instant>,
+    calls: usize,
+    passed: bool,
+}
+
+impl Deadline {
+    fn new(budget: GuessBudget) -> Self {
+        Deadline {
+            at: budget.max_time.map(|max_time| Instant::now() + max_time),
+            calls: 0,
+            passed: false,
+        }
+    }
+
+    fn passed(&mut self) -> bool {
+        self.calls += 1;
+        if !self.passed && self.calls.is_multiple_of(1024) {
+            self.passed = self.at.is_some_and(|at| Instant::now() > at);
+        }
+        self.passed
+    }
+}
+
 fn validate(
     bomb_subset: u128,
     boundary_constraints: &Vec<(u8, u128)>,
@@ -98,7 +129,7 @@ fn boundary_constraints(
                 let covered_neighbours = covered_neighbours(board, pos);
                 if !covered_neighbours.is_empty() {
                     let num_bombs = num_bombs_around(board, pos);
-                    let n = n - num_bombs;
+                    let n = n.saturating_sub(num_bombs);
                     let covered_neighbours_u128 =
                         tile_vec_to_u128(&covered_neighbours, covered_boundary);
                     return Some((n, covered_neighbours_u128));
@@ -109,11 +140,153 @@ fn boundary_constraints(
         .collect()
 }
 
-fn legal_scenario_info(
+// merging fewer pairs of arrangements than this isn't worth sharing between
+// threads
+const PARALLEL_PAIRS: usize = 1 << 14;
+
+// arrangements with each tile a bomb by number of bombs, then all arrangements
+// by number of bombs, which can be anything from none to every tile
+const NUM_BOMB_COUNTS: usize = GuessBudget::MAX_TILES + 1;
+type ScenarioCounts = (
+    [[usize; NUM_BOMB_COUNTS]; GuessBudget::MAX_TILES],
+    [usize; NUM_BOMB_COUNTS],
+);
+
+fn add_scenario(
+    (num_bombs_counters, total_num_bombs_counter): &mut ScenarioCounts,
+    bomb_subset: u128,
+) {
+    let num_bombs = bomb_subset.count_ones() as usize;
+    for (i, num_bombs_counters) in num_bombs_counters.iter_mut().enumerate() {
+        if bomb_subset & (1 << i) > 0 {
+            num_bombs_counters[num_bombs] += 1;
+        }
+    }
+    total_num_bombs_counter[num_bombs] += 1;
+}
+
+fn add_counts(
+    mut counts: Box<ScenarioCounts>,
+    other: Box<ScenarioCounts>,
+) -> Box<ScenarioCounts> {
+    for (counters, other_counters) in counts.0.iter_mut().zip(&other.0) {
+        for (count, other_count) in counters.iter_mut().zip(other_counters) {
+            *count += other_count;
+        }
+    }
+    for (count, other_count) in counts.1.iter_mut().zip(&other.1) {
+        *count += other_count;
+    }
+    counts
+}
+
+/// Every pair of arrangements from the two bins that still fits the numbers
+/// overlapping both, in the same order whether or not it's done in parallel
+fn merge_bins(
+    bin1: &[u128],
+    bin2: &[u128],
+    merging_constraints: &Vec<(u8, u128)>,
+    new_mask: u128,
+    deadline: &mut Deadline,
+    parallel: bool,
+) -> Option<Vec<u128>> {
+    if parallel && bin1.len() * bin2.len() >= PARALLEL_PAIRS {
+        let at = deadline.at;
+        return bin1
+            .par_iter()
+            .map(|&subset1| {
+                if at.is_some_and(|at| Instant::now() > at) {
+                    return None;
+                }
+                let merged = bin2
+                    .iter()
+                    .map(|&subset2| subset1 | subset2)
+                    .filter(|&combined_bomb_subset| {
+                        validate(
+                            combined_bomb_subset,
+                            merging_constraints,
+                            new_mask,
+                        )
+                    })
+                    .collect_vec();
+                Some(merged)
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|merged| merged.concat());
+    }
+    let mut new_bin = vec![];
+    for (subset1, subset2) in bin1.iter().cartesian_product(bin2) {
+        if deadline.passed() {
+            return None;
+        }
+        let combined_bomb_subset = subset1 | subset2;
+        if validate(combined_bomb_subset, merging_constraints, new_mask) {
+            new_bin.push(combined_bomb_subset);
+        }
+    }
+    Some(new_bin)
+}
+
+/// Folds `add` over the complete arrangements made from pairs from the two
+/// bins. Threads each start from `empty` and their results are combined with
+/// `merge`, so sums come out the same in parallel.
+fn fold_pairs<A: Send>(
+    bin1: &[u128],
+    bin2: &[u128],
+    boundary_constraints: &Vec<(u8, u128)>,
+    deadline: &mut Deadline,
+    parallel: bool,
+    (empty, add, merge): (
+        impl Fn() -> A + Sync + Send,
+        impl Fn(&mut A, u128) + Sync + Send,
+        impl Fn(A, A) -> A + Sync + Send,
+    ),
+) -> Option<A> {
+    if parallel && bin1.len() * bin2.len() >= PARALLEL_PAIRS {
+        let at = deadline.at;
+        return bin1
+            .par_iter()
+            .try_fold(&empty, |mut acc, &subset1| {
+                if at.is_some_and(|at| Instant::now() > at) {
+                    return None;
+                }
+                for &subset2 in bin2 {
+                    let bomb_subset = subset1 | subset2;
+                    if validate_final(bomb_subset, boundary_constraints) {
+                        add(&mut acc, bomb_subset);
+                    }
+                }
+                Some(acc)
+            })
+            .try_reduce(&empty, |acc, other| Some(merge(acc, other)));
+    }
+    let mut acc = empty();
+    for (subset1, subset2) in bin1.iter().cartesian_product(bin2) {
+        if deadline.passed() {
+            return None;
+        }
+        let bomb_subset = subset1 | subset2;
+        if validate_final(bomb_subset, boundary_constraints) {
+            add(&mut acc, bomb_subset);
+        }
+    }
+    Some(acc)
+}
+
+/// Folds over every arrangement of bombs on the boundary that fits the
+/// numbers, as `fold_pairs` does. `None` if the deadline passes first. With
+/// `parallel`, large merges are shared between threads.
+fn fold_scenarios<A: Send>(
     boundary_constraints: &Vec<(u8, u128)>,
     boundary_size: usize,
-) -> ([[usize; 100]; 128], [usize; 100]) {
-    let start = Instant::now();
+    deadline: &mut Deadline,
+    parallel: bool,
+    fold: (
+        impl Fn() -> A + Sync + Send,
+        impl Fn(&mut A, u128) + Sync + Send,
+        impl Fn(A, A) -> A + Sync + Send,
+    ),
+) -> Option<A> {
     let mut nbits_left = boundary_size;
     let mut bins = vec![];
     let nbins = if boundary_size <= 32 { 2 } else { 8 };
@@ -124,6 +297,9 @@ fn legal_scenario_info(
         let mut bin = vec![];
         let mask = max_chunk << nbits_left;
         for i in 0..=max_chunk {
+            if deadline.passed() {
+                return None;
+            }
             let bomb_subset = i << nbits_left;
             if validate(bomb_subset, boundary_constraints, mask) {
                 bin.push(bomb_subset);
@@ -134,7 +310,6 @@ fn legal_scenario_info(
     while bins.len() > 2 {
         let (bin1, mask1) = bins.pop().unwrap();
         let (bin2, mask2) = bins.pop().unwrap();
-        let mut new_bin = vec![];
         let new_mask = mask1 | mask2;
         let merging_constraints = boundary_constraints
             .iter()
@@ -144,12 +319,14 @@ fn legal_scenario_info(
                 subset & mask1 > 0 && subset & mask2 > 0
             })
             .collect_vec();
-        for (subset1, subset2) in bin1.iter().cartesian_product(bin2) {
-            let combined_bomb_subset = subset1 | subset2;
-            if validate(combined_bomb_subset, &merging_constraints, new_mask) {
-                new_bin.push(combined_bomb_subset);
-            }
-        }
+        let new_bin = merge_bins(
+            &bin1,
+            &bin2,
+            &merging_constraints,
+            new_mask,
+            deadline,
+            parallel,
+        )?;
         bins.insert(0, (new_bin, new_mask));
     }
     // final 2
@@ -163,111 +340,134 @@ fn legal_scenario_info(
         .filter(|(_, subset)| subset & mask1 > 0 && subset & mask2 > 0)
         .unique()
         .collect_vec();
+    fold_pairs(
+        &bin1,
+        &bin2,
+        &boundary_constraints,
+        deadline,
+        parallel,
+        fold,
+    )
+}
 
-    let mut num_bombs_counters = [[0; 100]; 128];
-    let mut total_num_bombs_counter = [0; 100];
-    let mut num_scenarios = 0;
-    for (subset1, subset2) in bin1.iter().cartesian_product(bin2) {
-        let bomb_subset = subset1 | subset2;
-        if validate_final(bomb_subset, &boundary_constraints) {
-            num_scenarios += 1;
-            let num_bombs = bomb_subset.count_ones() as usize;
-            for (i, num_bombs_counters) in
-                num_bombs_counters.iter_mut().enumerate()
-            {
-                if bomb_subset & (1 << i) > 0 {
-                    num_bombs_counters[num_bombs] += 1;
-                }
-            }
-
-            total_num_bombs_counter[num_bombs] += 1;
-        }
-    }
+/// Counts of the arrangements that fit the numbers, by number of bombs, in
+/// all and with each tile a bomb. `None` if the deadline passes first.
+fn legal_scenario_info(
+    boundary_constraints: &Vec<(u8, u128)>,
+    boundary_size: usize,
+    deadline: &mut Deadline,
+    parallel: bool,
+) -> Option<Box<ScenarioCounts>> {
+    let start = Instant::now();
+    let empty = || {
+        Box::new((
+            [[0; NUM_BOMB_COUNTS]; GuessBudget::MAX_TILES],
+            [0; NUM_BOMB_COUNTS],
+        ))
+    };
+    let counts = fold_scenarios(
+        boundary_constraints,
+        boundary_size,
+        deadline,
+        parallel,
+        (
+            empty,
+            |counts: &mut Box<ScenarioCounts>, bomb_subset| {
+                add_scenario(counts, bomb_subset)
+            },
+            add_counts,
+        ),
+    )?;
 
-    println!(
+    debug!(
         "Analysing legal scenarios took: {:>6.2}s ({:.1e} scenario(s) from {:>2} tiles)",
         start.elapsed().as_secs_f32(),
-        num_scenarios,
+        counts.1.iter().sum::<usize>(),
         boundary_size,
     );
-    (num_bombs_counters, total_num_bombs_counter)
+    Some(counts)
 }
 
-fn get_high_probability_guess(
-    covered_boundary: Vec<TilePos>,
-    all_covered: Vec<TilePos>,
+fn component_constraints(
     board: &Board,
-) -> Action {
-    // generate and test possible bombs positions around boundary
-    let covered_boundary = sensible_ordering(covered_boundary);
-    let boundary_constraints = boundary_constraints(board, &covered_boundary);
-    let total_num_bombs_left = board.num_bombs_left() as usize;
-    let num_non_boundary_covered = all_covered.len() - covered_boundary.len();
-    let (num_bombs_counters, mut total_num_bombs_counter) =
-        legal_scenario_info(&boundary_constraints, covered_boundary.len());
-    let max_bombs = total_num_bombs_left;
-    let min_bombs =
-        total_num_bombs_left.saturating_sub(num_non_boundary_covered);
-    let filter = |num_bombs_counter: &mut [usize; 100]| {
-        for (num_bombs, counter) in num_bombs_counter.iter_mut().enumerate() {
-            if num_bombs < min_bombs || max_bombs < num_bombs {
-                *counter = 0;
-            }
-        }
-    };
-    for mut num_bombs_counter in num_bombs_counters {
-        filter(&mut num_bombs_counter);
-    }
-    filter(&mut total_num_bombs_counter);
+    sub_boundary: &[TilePos],
+) -> Vec<(u8, u128)> {
+    // the other components' numbers don't touch these tiles
+    boundary_constraints(board, sub_boundary)
+        .into_iter()
+        .filter(|&(_, subset)| subset != 0)
+        .collect_vec()
+}
 
-    let (min_bombs, max_bombs) = {
-        let min_max = total_num_bombs_counter
-            .iter()
-            .enumerate()
-            .filter_map(|(num_bombs, &counter)| {
-                (counter > 0).then_some(num_bombs)
-            })
-            .minmax();
-        match min_max {
-            MinMaxResult::MinMax(min, max) => (min, max),
-            MinMaxResult::OneElement(val) => (val, val),
-            MinMaxResult::NoElements => panic!(),
-        }
-    };
-    let min_bombs_omitted = total_num_bombs_left - max_bombs;
-    let mut total_weights = 0.0;
-    let bombs_present_count = (0..=99)
-        .map(|num_bombs| {
-            if !(min_bombs <= num_bombs && num_bombs <= max_bombs) {
-                return (0, 0.0);
-            }
-            let weight = case_weight(
-                total_num_bombs_left - num_bombs,
-                num_non_boundary_covered,
-                min_bombs_omitted,
-            );
-            let count = total_num_bombs_counter[num_bombs];
-            total_weights += weight * count as f64;
-            (count, weight)
+/// Probability that each tile in the (ordered) covered boundary is safe,
+/// along with the probability that any non-boundary covered tile is safe, or
+/// `None` if no arrangement of bombs fits the board or it would take more than
+/// the budget to find out. Groups of tiles that share no numbers are
+/// enumerated separately.
+fn safety_probabilities(
+    covered_boundary: &[TilePos],
+    num_non_boundary_covered: usize,
+    board: &Board,
+    budget: GuessBudget,
+) -> Option<(Vec<f64>, Option<f64>)> {
+    let components = boundary_components(board, covered_boundary);
+    let max_tiles = budget.max_tiles.min(GuessBudget::MAX_TILES);
+    if components.iter().any(|(tiles, _)| tiles.len() > max_tiles) {
+        return None;
+    }
+    let mut deadline = Deadline::new(budget);
+    let components = components
+        .into_iter()
+        .map(|(tiles, _)| {
+            let sub_boundary = tiles
+                .iter()
+                .map(|&tile| covered_boundary[tile])
+                .collect_vec();
+            let boundary_constraints =
+                component_constraints(board, &sub_boundary);
+            let (num_bombs_counters, total_num_bombs_counter) =
+                *legal_scenario_info(
+                    &boundary_constraints,
+                    tiles.len(),
+                    &mut deadline,
+                    budget.parallel,
+                )?;
+            let size = tiles.len();
+            Component::normalised(
+                tiles,
+                total_num_bombs_counter[..=size]
+                    .iter()
+                    .map(|&count| count as f64)
+                    .collect(),
+                num_bombs_counters[..size]
+                    .iter()
+                    .map(|counters| {
+                        counters[..=size]
+                            .iter()
+                            .map(|&count| count as f64)
+                            .collect()
+                    })
+                    .collect(),
+            )
         })
-        .collect_vec();
+        .collect::<Option<Vec<_>>>()?;
+    combine_components(
+        &components,
+        covered_boundary.len(),
+        num_non_boundary_covered,
+        board,
+    )
+}
 
-    // evaluate legal bomb cases around boundary
-    let (boundary_tile, boundary_safety_prob) = covered_boundary
+fn best_guess(
+    covered_boundary: &[TilePos],
+    all_covered: &[TilePos],
+    (boundary_safety_probs, non_boundary_safety_prob): (Vec<f64>, Option<f64>),
+    board: &Board,
+) -> Action {
+    let (boundary_tile, &boundary_safety_prob) = covered_boundary
         .iter()
-        .enumerate()
-        .map(|(i, tile)| {
-            let unsafe_weights: f64 = num_bombs_counters[i]
-                .iter()
-                .enumerate()
-                .map(|(num_bombs, &count)| {
-                    let weight = bombs_present_count[num_bombs].1;
-                    weight * count as f64
-                })
-                .sum();
-            let proportion_safe = 1.0 - unsafe_weights / total_weights;
-            (tile, proportion_safe)
-        })
+        .zip(&boundary_safety_probs)
         .max_by(|(tile1, proportion_safe1), (tile2, proportion_safe2)| {
             proportion_safe1
                 .total_cmp(proportion_safe2)
@@ -275,51 +475,16 @@ fn get_high_probability_guess(
         })
         .unwrap();
 
-    if num_non_boundary_covered == 0 {
-        // println!(
-        //     "Best odds:                       {:>5.1}% -> {:?}",
-        //     boundary_safety_prob * 100.0,
-        //     boundary_tile,
-        // );
-        return Action::uncover(*boundary_tile);
-    }
-
     // consider if there are better odds for a non-boundary tile
-    let non_boundary_safety_prob = {
-        let unsafe_weights: f64 = bombs_present_count
-            .iter()
-            .enumerate()
-            .rev()
-            .map(|(num_bombs, &(count, weight))| {
-                if count == 0 {
-                    return 0.0;
-                }
-                let num_bombs_omitted = total_num_bombs_left - num_bombs;
-                count as f64 * weight * num_bombs_omitted as f64
-                    / num_non_boundary_covered as f64
-            })
-            .sum();
-        1.0 - unsafe_weights / total_weights
+    let non_boundary_safety_prob = match non_boundary_safety_prob {
+        Some(prob) => prob,
+        None => return Action::uncover(*boundary_tile),
     };
-    // println!(
-    //     "Best odds on boundary:           {:>5.1}% -> {:?}",
-    //     boundary_safety_prob * 100.0,
-    //     boundary_tile,
-    // );
-    // println!(
-    //     "Best odds not on boundary:       {:>5.1}%",
-    //     non_boundary_safety_prob * 100.0,
-    // );
     let &best_tile = if boundary_safety_prob > non_boundary_safety_prob {
-        // println!(
-        //     "Best odds are from boundary:     {:>5.1}% -> {:?}",
-        //     boundary_safety_prob * 100.0,
-        //     boundary_tile,
-        // );
         boundary_tile
     } else {
         // unwrap here because we have already checked non-boundary tiles exist
-        let non_boundary_tile = all_covered
+        all_covered
             .iter()
             .filter(|tile| !covered_boundary.contains(tile))
             .min_by_key(|&&tile| {
@@ -332,17 +497,318 @@ fn get_high_probability_guess(
                     tile,
                 )
             })
-            .unwrap();
-        // println!(
-        //     "Best odds are from non-boundary: {:>5.1}% -> {:?}",
-        //     non_boundary_safety_prob * 100.0,
-        //     non_boundary_tile,
-        // );
-        non_boundary_tile
+            .unwrap()
     };
     Action::uncover(best_tile)
 }
 
+// past this share of the safe tiles uncovered, guesses favour constrained
+// tiles over openings
+const PROGRESS_LATE: f64 = 0.5;
+
+/// Among tiles at most `tolerance` less safe than `guess`, picks the one most
+/// likely to open a region early in the game, and the one next to the most
+/// numbers late in the game
+fn progress_guess(
+    board: &Board,
+    guess: Action,
+    (covered_boundary, all_covered): (&[TilePos], &[TilePos]),
+    (boundary_safety_probs, non_boundary_safety_prob): (Vec<f64>, Option<f64>),
+    tolerance: f64,
+) -> Action {
+    if tolerance <= 0.0 {
+        return guess;
+    }
+    let boundary_safety_probs: HashMap<TilePos, f64> = covered_boundary
+        .iter()
+        .cloned()
+        .zip(boundary_safety_probs)
+        .collect();
+    let safety_prob = |tile: &TilePos| match boundary_safety_probs.get(tile) {
+        Some(&prob) => prob,
+        None => non_boundary_safety_prob.unwrap_or(0.0),
+    };
+    let num_safe = board.width() * board.height() - board.num_bombs_total();
+    let progress =
+        1.0 - board.safe_tiles_remaining() as f64 / num_safe.max(1) as f64;
+    let score = |tile: &TilePos| {
+        if progress < PROGRESS_LATE {
+            // chance of a zero, times the tiles it would then open
+            let neighbours = covered_neighbours(board, *tile);
+            let all_safe: f64 = neighbours.iter().map(safety_prob).product();
+            all_safe * neighbours.len() as f64
+        } else {
+            uncovered_neighbours(board, *tile).len() as f64
+        }
+    };
+    let best_safety_prob = safety_prob(&guess.pos);
+    all_covered
+        .iter()
+        .map(|tile| (tile, safety_prob(tile)))
+        .filter(|&(_, prob)| prob >= best_safety_prob - tolerance)
+        .map(|(tile, prob)| (tile, prob, score(tile)))
+        .max_by(|&(tile1, prob1, score1), &(tile2, prob2, score2)| {
+            score1
+                .total_cmp(&score2)
+                .then(prob1.total_cmp(&prob2))
+                .then(tile2.cmp(tile1))
+        })
+        .map(|(&tile, _, _)| Action::uncover(tile))
+        .unwrap_or(guess)
+}
+
+/// Probability that each covered tile is a bomb, boundary tiles are evaluated
+/// exactly while all other covered tiles share a single estimate
+pub fn tile_probabilities(
+    board: &Board,
+    budget: GuessBudget,
+) -> HashMap<TilePos, f64> {
+    let (all_covered, covered_boundary) = covered_tiles(board);
+    let uniform = |all_covered: Vec<TilePos>| {
+        let bomb_prob =
+            board.num_bombs_left().max(0) as f64 / all_covered.len() as f64;
+        all_covered
+            .into_iter()
+            .map(|tile| (tile, bomb_prob.min(1.0)))
+            .collect()
+    };
+    if all_covered.is_empty() {
+        return HashMap::new();
+    }
+    // nothing to enumerate (or misflagged), so spread bombs evenly
+    if covered_boundary.is_empty() || board.num_bombs_left() < 0 {
+        return uniform(all_covered);
+    }
+    let covered_boundary = sensible_ordering(covered_boundary);
+    let num_non_boundary_covered = all_covered.len() - covered_boundary.len();
+    let Some((boundary_safety_probs, non_boundary_safety_prob)) =
+        safety_probabilities(
+            &covered_boundary,
+            num_non_boundary_covered,
+            board,
+            budget,
+        )
+    else {
+        return uniform(all_covered);
+    };
+    let mut probabilities: HashMap<TilePos, f64> = covered_boundary
+        .iter()
+        .zip(boundary_safety_probs)
+        .map(|(&tile, safety_prob)| (tile, 1.0 - safety_prob))
+        .collect();
+    if let Some(safety_prob) = non_boundary_safety_prob {
+        for tile in all_covered {
+            probabilities.entry(tile).or_insert(1.0 - safety_prob);
+        }
+    }
+    probabilities
+}
+
+/// Sorted pairs of boundary tiles that are each a bomb half the time, with
+/// exactly one of them a bomb in every arrangement that fits the numbers, so
+/// no amount of deduction can tell them apart. Tiles left to a coinflip only
+/// by the number of bombs remaining aren't found. `None` if a group of
+/// boundary tiles is too large to enumerate in the budget, or the deadline
+/// passes first.
+pub fn detect_coinflips(
+    board: &Board,
+    budget: GuessBudget,
+) -> Option<Vec<(TilePos, TilePos)>> {
+    let probabilities = tile_probabilities(board, budget);
+    let (_, covered_boundary) = covered_tiles(board);
+    let covered_boundary = sensible_ordering(covered_boundary);
+    // the odds are normalised in floating point
+    let even_odds = |tile: &TilePos| {
+        probabilities
+            .get(tile)
+            .is_some_and(|prob| (prob - 0.5).abs() < 1e-9)
+    };
+    let max_tiles = budget.max_tiles.min(GuessBudget::MAX_TILES);
+    let mut deadline = Deadline::new(budget);
+    let mut coinflips = vec![];
+    for (tiles, _) in boundary_components(board, &covered_boundary) {
+        let sub_boundary = tiles
+            .iter()
+            .map(|&tile| covered_boundary[tile])
+            .collect_vec();
+        let candidates = (0..sub_boundary.len())
+            .filter(|&i| even_odds(&sub_boundary[i]))
+            .collect_vec();
+        if candidates.len() < 2 {
+            continue;
+        }
+        if sub_boundary.len() > max_tiles {
+            return None;
+        }
+        let constraints = component_constraints(board, &sub_boundary);
+        // arrangements in all, with each candidate a bomb, and with each pair
+        // of candidates both bombs
+        let k = candidates.len();
+        let (total, bombs, both) = fold_scenarios(
+            &constraints,
+            sub_boundary.len(),
+            &mut deadline,
+            budget.parallel,
+            (
+                || (0, vec![0; k], vec![0; k * k]),
+                |(total, bombs, both): &mut (usize, Vec<usize>, Vec<usize>),
+                 bomb_subset: u128| {
+                    *total += 1;
+                    let is_bomb =
+                        |&x: &usize| bomb_subset & 1 << candidates[x] > 0;
+                    for x in (0..k).filter(is_bomb) {
+                        bombs[x] += 1;
+                        for y in (x + 1..k).filter(is_bomb) {
+                            both[x * k + y] += 1;
+                        }
+                    }
+                },
+                |(total, mut bombs, mut both),
+                 (other_total, other_bombs, other_both)| {
+                    bombs
+                        .iter_mut()
+                        .zip(other_bombs)
+                        .for_each(|(a, b)| *a += b);
+                    both.iter_mut().zip(other_both).for_each(|(a, b)| *a += b);
+                    (total + other_total, bombs, both)
+                },
+            ),
+        )?;
+        for (x, y) in (0..k).tuple_combinations() {
+            // never both bombs, and between them a bomb in every arrangement
+            if both[x * k + y] == 0 && bombs[x] + bombs[y] == total {
+                let (a, b) =
+                    (sub_boundary[candidates[x]], sub_boundary[candidates[y]]);
+                coinflips.push((a.min(b), a.max(b)));
+            }
+        }
+    }
+    coinflips.sort();
+    Some(coinflips)
+}
+
+/// Bombs among the covered tiles in a random arrangement that fits the
+/// numbers and the bombs left, each such arrangement equally likely. Each
+/// boundary tile is settled in turn from the counts of what's left once the
+/// earlier ones are fixed. `None` if nothing fits, or a group of boundary
+/// tiles is too large to enumerate in the budget.
+pub(super) fn sample_layout(
+    board: &Board,
+    rng: &mut impl Rng,
+) -> Option<Vec<TilePos>> {
+    let budget = GuessBudget::default();
+    let (all_covered, covered_boundary) = covered_tiles(board);
+    let covered_boundary = sensible_ordering(covered_boundary);
+    let non_boundary = all_covered
+        .into_iter()
+        .filter(|pos| !covered_boundary.contains(pos))
+        .collect_vec();
+    let mut deadline = Deadline::new(budget);
+    let components = boundary_components(board, &covered_boundary)
+        .into_iter()
+        .map(|(tiles, _)| {
+            if tiles.len() > budget.max_tiles {
+                return None;
+            }
+            let sub_boundary = tiles
+                .iter()
+                .map(|&tile| covered_boundary[tile])
+                .collect_vec();
+            let constraints = component_constraints(board, &sub_boundary);
+            let counts = legal_scenario_info(
+                &constraints,
+                sub_boundary.len(),
+                &mut deadline,
+                budget.parallel,
+            )?;
+            // scaled like `Component` so the products stay small
+            let num_arrangements: usize = counts.1.iter().sum();
+            let counts = counts.1[..=sub_boundary.len()]
+                .iter()
+                .map(|&count| count as f64 / num_arrangements as f64)
+                .collect_vec();
+            Some((sub_boundary, constraints, counts))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // bomb counts of the components from each one on
+    let mut suffixes = vec![vec![1.0]];
+    for (_, _, counts) in components.iter().rev() {
+        let last = suffixes.last().unwrap();
+        let mut next = vec![0.0; last.len() + counts.len() - 1];
+        for (i, x) in last.iter().enumerate() {
+            for (j, y) in counts.iter().enumerate() {
+                next[i + j] += x * y;
+            }
+        }
+        suffixes.push(next);
+    }
+    suffixes.reverse();
+    let num_bombs_left = board.num_bombs_left().max(0) as usize;
+    let feasible = |num_bombs: usize| {
+        num_bombs <= num_bombs_left
+            && num_bombs_left - num_bombs <= non_boundary.len()
+    };
+    let max_bombs = (0..suffixes[0].len()).rev().find(|&num_bombs| {
+        suffixes[0][num_bombs] > 0.0 && feasible(num_bombs)
+    })?;
+    let weight = |num_bombs: usize| {
+        if !feasible(num_bombs) {
+            return 0.0;
+        }
+        case_weight(
+            num_bombs_left - num_bombs,
+            non_boundary.len(),
+            num_bombs_left - max_bombs,
+        )
+    };
+
+    let mut bombs = vec![];
+    let mut num_boundary_bombs = 0;
+    for (c, (sub_boundary, mut constraints, counts)) in
+        components.into_iter().enumerate()
+    {
+        // how many bombs this component holds, given those before it
+        let rest = &suffixes[c + 1];
+        let odds = counts
+            .iter()
+            .enumerate()
+            .map(|(k, count)| {
+                let ways: f64 = rest
+                    .iter()
+                    .enumerate()
+                    .map(|(j, other)| {
+                        other * weight(num_boundary_bombs + k + j)
+                    })
+                    .sum();
+                count * ways
+            })
+            .collect_vec();
+        let k = WeightedIndex::new(&odds).ok()?.sample(rng);
+        num_boundary_bombs += k;
+        let all_tiles = u128::MAX >> (128 - sub_boundary.len());
+        constraints.push((k as u8, all_tiles));
+        for (i, &pos) in sub_boundary.iter().enumerate() {
+            let (num_bombs_counters, total_num_bombs_counter) =
+                *legal_scenario_info(
+                    &constraints,
+                    sub_boundary.len(),
+                    &mut deadline,
+                    budget.parallel,
+                )?;
+            let bomb = rng.gen_range(0..total_num_bombs_counter[k])
+                < num_bombs_counters[i][k];
+            if bomb {
+                bombs.push(pos);
+            }
+            constraints.push((bomb as u8, 1 << i));
+        }
+    }
+    let rest = num_bombs_left - num_boundary_bombs;
+    bombs.extend(non_boundary.choose_multiple(rng, rest));
+    Some(bombs)
+}
+
 fn sensible_ordering(covered_boundary: Vec<TilePos>) -> Vec<TilePos> {
     if covered_boundary.len() <= 1 {
         return covered_boundary.to_vec();
@@ -375,13 +841,13 @@ fn sensible_ordering(covered_boundary: Vec<TilePos>) -> Vec<TilePos> {
     boundary1
 }
 
-pub fn make_guess(board: &Board) -> Action {
-    // if we're out of ideas, just permute until we find a compatible option
+/// All covered tiles, and those of them on the boundary with uncovered tiles
+fn covered_tiles(board: &Board) -> (Vec<TilePos>, Vec<TilePos>) {
     let all_covered = (0..board.width())
         .cartesian_product(0..board.height())
         .filter_map(|(col, row)| {
             let pos = TilePos { col, row };
-            (board.tile_state(pos) == TileState::Covered).then_some(pos)
+            board.tile_state(pos).covered().then_some(pos)
         })
         .collect_vec();
     let covered_boundary = all_covered
@@ -389,21 +855,51 @@ pub fn make_guess(board: &Board) -> Action {
         .filter(|&&pos| !uncovered_neighbours(board, pos).is_empty())
         .cloned()
         .collect_vec();
+    (all_covered, covered_boundary)
+}
+
+/// The guess, along with the odds it's safe unless it's only a rough one
+pub fn make_guess(
+    board: &Board,
+    budget: GuessBudget,
+    progress_tolerance: f64,
+) -> (Action, Option<f64>) {
+    let (all_covered, covered_boundary) = covered_tiles(board);
 
     if covered_boundary.is_empty() {
-        let &tile = all_covered.first().unwrap();
-        return Action::uncover(tile);
+        return first_covered(board, &all_covered);
     }
 
-    if covered_boundary.len() <= 128 {
-        return get_high_probability_guess(
-            covered_boundary,
-            all_covered,
-            board,
-        );
+    let ordered_boundary = sensible_ordering(covered_boundary.clone());
+    let num_non_boundary_covered = all_covered.len() - covered_boundary.len();
+    match safety_probabilities(
+        &ordered_boundary,
+        num_non_boundary_covered,
+        board,
+        budget,
+    ) {
+        Some(safety_probs) => {
+            let guess = best_guess(
+                &ordered_boundary,
+                &all_covered,
+                safety_probs.clone(),
+                board,
+            );
+            let guess = progress_guess(
+                board,
+                guess,
+                (&ordered_boundary, &all_covered),
+                safety_probs.clone(),
+                progress_tolerance,
+            );
+            with_safety(guess, &ordered_boundary, &safety_probs)
+        }
+        None => (rough_guess(board, &covered_boundary), None),
     }
+}
 
-    // this will almost certainly never happen, but it's an option
+// if we're out of ideas (or time), go by the densest subset each tile is in
+fn rough_guess(board: &Board, covered_boundary: &[TilePos]) -> Action {
     let (min_bombs, max_bombs) = deductions::get_subset_bounds(board);
     let pos = covered_boundary
         .iter()
@@ -421,6 +917,488 @@ pub fn make_guess(board: &Board) -> Action {
                 .unwrap()
         })
         .unwrap();
-    println!("Guessing: ({}, {})", pos.col, pos.row);
+    debug!("Guessing: ({}, {})", pos.col, pos.row);
     Action::uncover(*pos)
 }
+
+// the odds the guess was chosen by, that it's safe
+fn with_safety(
+    guess: Action,
+    covered_boundary: &[TilePos],
+    (boundary_safety_probs, non_boundary_safety_prob): &(Vec<f64>, Option<f64>),
+) -> (Action, Option<f64>) {
+    let safety_prob =
+        match covered_boundary.iter().position(|&tile| tile == guess.pos) {
+            Some(i) => Some(boundary_safety_probs[i]),
+            None => *non_boundary_safety_prob,
+        };
+    (guess, safety_prob)
+}
+
+// with nothing to go on, every covered tile is as likely as any other
+fn first_covered(
+    board: &Board,
+    all_covered: &[TilePos],
+) -> (Action, Option<f64>) {
+    let bomb_prob =
+        board.num_bombs_left().max(0) as f64 / all_covered.len() as f64;
+    (
+        Action::uncover(all_covered[0]),
+        Some(1.0 - bomb_prob.min(1.0)),
+    )
+}
+
+/// Same as `make_guess`, but finds exact probabilities for any size of
+/// boundary by solving each independent part of it separately
+pub fn make_guess_csp(
+    board: &Board,
+    budget: GuessBudget,
+    progress_tolerance: f64,
+) -> (Action, Option<f64>) {
+    let (all_covered, covered_boundary) = covered_tiles(board);
+    if covered_boundary.is_empty() {
+        return first_covered(board, &all_covered);
+    }
+    let ordered_boundary = sensible_ordering(covered_boundary.clone());
+    let num_non_boundary_covered = all_covered.len() - covered_boundary.len();
+    match csp_safety_probabilities(
+        &ordered_boundary,
+        num_non_boundary_covered,
+        board,
+        budget,
+    ) {
+        Some(safety_probs) => {
+            let guess = best_guess(
+                &ordered_boundary,
+                &all_covered,
+                safety_probs.clone(),
+                board,
+            );
+            let guess = progress_guess(
+                board,
+                guess,
+                (&ordered_boundary, &all_covered),
+                safety_probs.clone(),
+                progress_tolerance,
+            );
+            with_safety(guess, &ordered_boundary, &safety_probs)
+        }
+        None => (rough_guess(board, &covered_boundary), None),
+    }
+}
+
+// how many of the safest boundary tiles to weigh up, and how much less safe
+// than the safest they may be
+const INFORMATION_GAIN_CANDIDATES: usize = 5;
+const INFORMATION_GAIN_TOLERANCE: f64 = 0.005;
+
+/// Same as `make_guess_csp`, but among boundary tiles almost as safe as the
+/// safest picks the one expected to force the most deductions
+pub fn make_guess_information_gain(
+    board: &Board,
+    budget: GuessBudget,
+) -> (Action, Option<f64>) {
+    let (all_covered, covered_boundary) = covered_tiles(board);
+    if covered_boundary.is_empty() {
+        return first_covered(board, &all_covered);
+    }
+    let ordered_boundary = sensible_ordering(covered_boundary.clone());
+    let num_non_boundary_covered = all_covered.len() - covered_boundary.len();
+    let Some(safety_probs) = csp_safety_probabilities(
+        &ordered_boundary,
+        num_non_boundary_covered,
+        board,
+        budget,
+    ) else {
+        return (rough_guess(board, &covered_boundary), None);
+    };
+    let guess = best_guess(
+        &ordered_boundary,
+        &all_covered,
+        safety_probs.clone(),
+        board,
+    );
+    // an opening away from the boundary is already the safest move
+    if !ordered_boundary.contains(&guess.pos) {
+        return with_safety(guess, &ordered_boundary, &safety_probs);
+    }
+    let (boundary_safety_probs, non_boundary_safety_prob) = &safety_probs;
+    let bomb_probs: HashMap<TilePos, f64> = ordered_boundary
+        .iter()
+        .zip(boundary_safety_probs)
+        .map(|(&tile, safety_prob)| (tile, 1.0 - safety_prob))
+        .collect();
+    let bomb_prob = |tile: &TilePos| match bomb_probs.get(tile) {
+        Some(&prob) => prob,
+        None => 1.0 - non_boundary_safety_prob.unwrap_or(1.0),
+    };
+    let best_safety_prob = 1.0 - bomb_prob(&guess.pos);
+    let guess = ordered_boundary
+        .iter()
+        .zip(boundary_safety_probs.iter().copied())
+        .filter(|&(_, safety_prob)| {
+            safety_prob >= best_safety_prob - INFORMATION_GAIN_TOLERANCE
+        })
+        .sorted_by(|(_, safety_prob1), (_, safety_prob2)| {
+            safety_prob2.total_cmp(safety_prob1)
+        })
+        .take(INFORMATION_GAIN_CANDIDATES)
+        .max_by(|&(tile1, safety_prob1), &(tile2, safety_prob2)| {
+            expected_forced(board, tile1, bomb_prob)
+                .total_cmp(&expected_forced(board, tile2, bomb_prob))
+                .then(safety_prob1.total_cmp(&safety_prob2))
+                .then(tile2.cmp(tile1))
+        })
+        .map(|(&tile, _)| Action::uncover(tile))
+        .unwrap_or(guess);
+    with_safety(guess, &ordered_boundary, &safety_probs)
+}
+
+// covered neighbours settled by the number under the tile, which happens when
+// they're all safe or all bombs. Treats them as independent, so it's only an
+// estimate.
+fn expected_forced(
+    board: &Board,
+    tile: &TilePos,
+    bomb_prob: impl Fn(&TilePos) -> f64,
+) -> f64 {
+    let neighbours = covered_neighbours(board, *tile);
+    let all_safe: f64 = neighbours.iter().map(|n| 1.0 - bomb_prob(n)).product();
+    let all_bombs: f64 = neighbours.iter().map(bomb_prob).product();
+    (all_safe + all_bombs) * neighbours.len() as f64
+}
+
+// bombs still to find among these boundary tiles, by index
+type Constraint = (usize, Vec<usize>);
+
+/// Bomb arrangements of one connected part of the boundary, scaled so the
+/// counts sum to 1
+struct Component {
+    tiles: Vec<usize>,
+    // arrangements by number of bombs
+    counts: Vec<f64>,
+    // arrangements with each tile a bomb, by number of bombs
+    tile_counts: Vec<Vec<f64>>,
+}
+
+impl Component {
+    /// `None` if there are no arrangements at all
+    fn normalised(
+        tiles: Vec<usize>,
+        mut counts: Vec<f64>,
+        mut tile_counts: Vec<Vec<f64>>,
+    ) -> Option<Component> {
+        let num_arrangements: f64 = counts.iter().sum();
+        if num_arrangements == 0.0 {
+            return None;
+        }
+        // keep the numbers small when many components are multiplied together
+        for count in counts.iter_mut().chain(tile_counts.iter_mut().flatten()) {
+            *count /= num_arrangements;
+        }
+        Some(Component {
+            tiles,
+            counts,
+            tile_counts,
+        })
+    }
+}
+
+/// Splits the boundary into groups of tiles that share no numbers with the
+/// rest, as indices into `covered_boundary` in the same order. Each group
+/// comes with its numbers: bombs still to find and the tiles they're among.
+fn boundary_components(
+    board: &Board,
+    covered_boundary: &[TilePos],
+) -> Vec<(Vec<usize>, Vec<Constraint>)> {
+    let index: HashMap<TilePos, usize> = covered_boundary
+        .iter()
+        .enumerate()
+        .map(|(i, &tile)| (tile, i))
+        .collect();
+    let constraints = (0..board.width())
+        .cartesian_product(0..board.height())
+        .filter_map(|(col, row)| {
+            let pos = TilePos { col, row };
+            let TileState::UncoveredSafe(n) = board.tile_state(pos) else {
+                return None;
+            };
+            let tiles = covered_neighbours(board, pos)
+                .iter()
+                .map(|tile| index[tile])
+                .collect_vec();
+            let n = n.saturating_sub(num_bombs_around(board, pos));
+            (!tiles.is_empty()).then_some((n as usize, tiles))
+        })
+        .collect_vec();
+
+    // union find, tiles in a constraint together belong to the same component
+    let mut parent = (0..covered_boundary.len()).collect_vec();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (_, tiles) in &constraints {
+        for &tile in &tiles[1..] {
+            let (a, b) = (root(&mut parent, tiles[0]), root(&mut parent, tile));
+            parent[a] = b;
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for tile in 0..covered_boundary.len() {
+        groups
+            .entry(root(&mut parent, tile))
+            .or_default()
+            .push(tile);
+    }
+    let mut constraints_by_group: HashMap<usize, Vec<Constraint>> =
+        HashMap::new();
+    for constraint in constraints {
+        let group = root(&mut parent, constraint.1[0]);
+        constraints_by_group
+            .entry(group)
+            .or_default()
+            .push(constraint);
+    }
+    groups
+        .into_iter()
+        .map(|(group, tiles)| {
+            let constraints =
+                constraints_by_group.remove(&group).unwrap_or_default();
+            (tiles, constraints)
+        })
+        .sorted()
+        .collect()
+}
+
+/// Safety probabilities as in `safety_probabilities`, from components
+/// combined by how many bombs each holds
+fn combine_components(
+    components: &[Component],
+    boundary_size: usize,
+    num_non_boundary_covered: usize,
+    board: &Board,
+) -> Option<(Vec<f64>, Option<f64>)> {
+    let total_num_bombs_left = board.num_bombs_left().max(0) as usize;
+    // bomb counts of every component but one, from running products
+    let convolve = |a: &[f64], b: &[f64]| {
+        let mut out = vec![0.0; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += x * y;
+            }
+        }
+        out
+    };
+    let mut prefixes = vec![vec![1.0]];
+    for component in components {
+        let next = convolve(prefixes.last().unwrap(), &component.counts);
+        prefixes.push(next);
+    }
+    let mut suffixes = vec![vec![1.0]];
+    for component in components.iter().rev() {
+        let next = convolve(suffixes.last().unwrap(), &component.counts);
+        suffixes.push(next);
+    }
+    suffixes.reverse();
+    let total = prefixes.last().unwrap();
+
+    // weight each total by the ways to place the rest of the bombs elsewhere
+    let feasible = |num_bombs: usize| {
+        num_bombs <= total_num_bombs_left
+            && total_num_bombs_left - num_bombs <= num_non_boundary_covered
+    };
+    let max_bombs = (0..total.len())
+        .rev()
+        .find(|&num_bombs| total[num_bombs] > 0.0 && feasible(num_bombs))?;
+    let min_bombs_omitted = total_num_bombs_left - max_bombs;
+    let weights = (0..total.len())
+        .map(|num_bombs| {
+            if !feasible(num_bombs) {
+                return 0.0;
+            }
+            case_weight(
+                total_num_bombs_left - num_bombs,
+                num_non_boundary_covered,
+                min_bombs_omitted,
+            )
+        })
+        .collect_vec();
+    let total_weights: f64 =
+        total.iter().zip(&weights).map(|(count, w)| count * w).sum();
+    if total_weights <= 0.0 {
+        return None;
+    }
+
+    let mut boundary_safety_probs = vec![0.0; boundary_size];
+    for (c, component) in components.iter().enumerate() {
+        let rest = convolve(&prefixes[c], &suffixes[c + 1]);
+        for (tile, tile_counts) in
+            component.tiles.iter().zip(&component.tile_counts)
+        {
+            let unsafe_weights: f64 = tile_counts
+                .iter()
+                .enumerate()
+                .flat_map(|(k, count)| {
+                    rest.iter()
+                        .enumerate()
+                        .map(move |(j, other)| (k + j, count * other))
+                })
+                .map(|(num_bombs, count)| count * weights[num_bombs])
+                .sum();
+            boundary_safety_probs[*tile] = 1.0 - unsafe_weights / total_weights;
+        }
+    }
+
+    if num_non_boundary_covered == 0 {
+        return Some((boundary_safety_probs, None));
+    }
+    // every non-boundary tile is equally likely to be safe
+    let unsafe_weights: f64 = total
+        .iter()
+        .zip(&weights)
+        .enumerate()
+        .map(|(num_bombs, (count, weight))| {
+            let num_bombs_omitted =
+                total_num_bombs_left.saturating_sub(num_bombs);
+            count * weight * num_bombs_omitted as f64
+                / num_non_boundary_covered as f64
+        })
+        .sum();
+    let non_boundary_safety_prob = 1.0 - unsafe_weights / total_weights;
+    Some((boundary_safety_probs, Some(non_boundary_safety_prob)))
+}
+
+/// Like `safety_probabilities`, but backtracks through each component instead
+/// of enumerating bit masks, so only the time budget applies
+fn csp_safety_probabilities(
+    covered_boundary: &[TilePos],
+    num_non_boundary_covered: usize,
+    board: &Board,
+    budget: GuessBudget,
+) -> Option<(Vec<f64>, Option<f64>)> {
+    let start = Instant::now();
+    let mut deadline = Deadline::new(budget);
+    let total_num_bombs_left = board.num_bombs_left().max(0) as usize;
+    let components = boundary_components(board, covered_boundary)
+        .into_iter()
+        .map(|(tiles, constraints)| {
+            solve_component(
+                tiles,
+                &constraints,
+                total_num_bombs_left,
+                &mut deadline,
+            )
+        })
+        .collect::<Option<Vec<_>>>()?;
+    debug!(
+        "Solving {} boundary component(s) took: {:>6.2}s ({:>2} tiles)",
+        components.len(),
+        start.elapsed().as_secs_f32(),
+        covered_boundary.len(),
+    );
+    combine_components(
+        &components,
+        covered_boundary.len(),
+        num_non_boundary_covered,
+        board,
+    )
+}
+
+/// Backtracks through every arrangement of bombs on the component's tiles
+/// that satisfies its numbers, or `None` if there isn't one or the deadline
+/// passes first
+fn solve_component(
+    tiles: Vec<usize>,
+    constraints: &[Constraint],
+    max_bombs: usize,
+    deadline: &mut Deadline,
+) -> Option<Component> {
+    let local: HashMap<usize, usize> = tiles
+        .iter()
+        .enumerate()
+        .map(|(i, &tile)| (tile, i))
+        .collect();
+    let mut tile_constraints = vec![vec![]; tiles.len()];
+    let mut remaining = vec![];
+    for (c, (n, subset)) in constraints.iter().enumerate() {
+        for tile in subset {
+            tile_constraints[local[tile]].push(c);
+        }
+        remaining.push((*n as isize, subset.len()));
+    }
+    let mut component = Component {
+        counts: vec![0.0; tiles.len() + 1],
+        tile_counts: vec![vec![0.0; tiles.len() + 1]; tiles.len()],
+        tiles,
+    };
+    let mut search = Backtrack {
+        max_bombs,
+        tile_constraints,
+        remaining,
+        bombs: vec![false; component.tiles.len()],
+        component: &mut component,
+        deadline,
+    };
+    search.run(0, 0);
+    if deadline.passed {
+        return None;
+    }
+    Component::normalised(
+        component.tiles,
+        component.counts,
+        component.tile_counts,
+    )
+}
+
+// state shared down the recursion of `solve_component`
+struct Backtrack<'a> {
+    max_bombs: usize,
+    tile_constraints: Vec<Vec<usize>>,
+    // bombs still needed and tiles still unassigned for each constraint
+    remaining: Vec<(isize, usize)>,
+    bombs: Vec<bool>,
+    component: &'a mut Component,
+    deadline: &'a mut Deadline,
+}
+
+impl Backtrack<'_> {
+    fn run(&mut self, i: usize, num_bombs: usize) {
+        if self.deadline.passed() {
+            return;
+        }
+        if i == self.bombs.len() {
+            self.component.counts[num_bombs] += 1.0;
+            let bombs = self.bombs.iter().enumerate().filter(|(_, &bomb)| bomb);
+            for (tile, _) in bombs {
+                self.component.tile_counts[tile][num_bombs] += 1.0;
+            }
+            return;
+        }
+        for bomb in [false, true] {
+            if bomb && num_bombs == self.max_bombs {
+                continue;
+            }
+            let mut possible = true;
+            for &c in &self.tile_constraints[i] {
+                let (needed, unassigned) = &mut self.remaining[c];
+                *needed -= bomb as isize;
+                *unassigned -= 1;
+                possible &= *needed >= 0 && *needed as usize <= *unassigned;
+            }
+            if possible {
+                self.bombs[i] = bomb;
+                self.run(i + 1, num_bombs + bomb as usize);
+            }
+            for &c in &self.tile_constraints[i] {
+                let (needed, unassigned) = &mut self.remaining[c];
+                *needed += bomb as isize;
+                *unassigned += 1;
+            }
+        }
+        self.bombs[i] = false;
+    }
+}
@@ -1,10 +1,12 @@
 use bevy::{prelude::*, window::PrimaryWindow};
 
 use crate::{
-    board::{Action, ActionResult, ActionType, Board, TileState},
+    board::{Action, ActionResult, ActionType, Board, TilePos, TileState},
+    replay::Replay,
     setup::UISizing,
-    AgentState, BotButton, Difficulty, FaceButton, FaceButtonState, GameState,
-    Record,
+    AgentState, AutoFlag, AutoLoop, BotButton, BotSpeed, CameraView, Cursor,
+    Difficulty, FaceButton, FaceButtonState, GameState, GameTimer, Paused,
+    Playback, SafeChord, Stats, TileChanged,
 };
 
 pub mod agent;
@@ -13,102 +15,418 @@ pub fn restart(
     mut q_board: Query<&mut Board>,
     mut next_app_state: ResMut<NextState<GameState>>,
     app_state: ResMut<State<GameState>>,
-    mut q_record: Query<&mut Record>,
+    mut stats: ResMut<Stats>,
+    mut cursor: ResMut<Cursor>,
+    mut replay: ResMut<Replay>,
+    same_seed: bool,
 ) {
     let mut board = q_board.single_mut();
+    cursor.reset();
     // avoid repeated restart
     if !board.first_uncovered() {
         return;
     }
-    let mut record = q_record.single_mut();
     if matches!(app_state.get(), GameState::Playing) {
-        end_game(&mut record, &ActionResult::Continue, &board);
+        end_game(&mut stats, &ActionResult::Continue, &board);
     } else {
         next_app_state.set(GameState::Playing);
     }
-    board.reset(None);
+    let seed = same_seed.then(|| board.initial_seed());
+    board.reset(seed);
+    replay.clear();
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn check_auto_loop(
+    q_board: Query<&mut Board>,
+    next_app_state: ResMut<NextState<GameState>>,
+    mut next_agent_state: ResMut<NextState<AgentState>>,
+    app_state: ResMut<State<GameState>>,
+    stats: ResMut<Stats>,
+    cursor: ResMut<Cursor>,
+    replay: ResMut<Replay>,
+    mut auto_loop: ResMut<AutoLoop>,
+    paused: Res<Paused>,
+    time: Res<Time>,
+) {
+    if paused.0 || !auto_loop.restart_due(time.delta()) {
+        return;
+    }
+    restart(
+        q_board,
+        next_app_state,
+        app_state,
+        stats,
+        cursor,
+        replay,
+        false,
+    );
+    next_agent_state.set(AgentState::Thinking);
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn check_restart(
+    mut commands: Commands,
     difficulty: Res<State<Difficulty>>,
     mut next_difficulty: ResMut<NextState<Difficulty>>,
     mut next_app_state: ResMut<NextState<GameState>>,
     mut next_agent_state: ResMut<NextState<AgentState>>,
     app_state: ResMut<State<GameState>>,
-    mut q_face_buttons: Query<(&FaceButton, &crate::Button)>,
+    q_face_buttons: Query<(&FaceButton, &crate::Button)>,
     mouse: Res<Input<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    (q_windows, view): (Query<&Window, With<PrimaryWindow>>, Res<CameraView>),
     q_board: Query<&mut Board>,
-    q_record: Query<&mut Record>,
+    stats: ResMut<Stats>,
+    mut cursor: ResMut<Cursor>,
+    mut replay: ResMut<Replay>,
+    keys: Res<Input<KeyCode>>,
 ) {
-    for (&FaceButton(new_difficulty), button) in &mut q_face_buttons {
-        if button.just_released(q_windows.single(), &mouse) {
-            next_agent_state.set(AgentState::Resting);
-            if new_difficulty != **difficulty {
-                next_difficulty.set(new_difficulty);
-                next_app_state.set(GameState::Playing);
-            } else {
-                restart(q_board, next_app_state, app_state, q_record);
-            }
-            return;
-        }
+    // number keys switch difficulty like the face buttons, but never restart
+    let key_difficulty = [
+        (KeyCode::Key1, Difficulty::Easy),
+        (KeyCode::Key2, Difficulty::Medium),
+        (KeyCode::Key3, Difficulty::Hard),
+    ]
+    .into_iter()
+    .find(|&(key, new_difficulty)| {
+        keys.just_pressed(key) && new_difficulty != **difficulty
+    })
+    .map(|(_, new_difficulty)| new_difficulty);
+    let clicked_difficulty = q_face_buttons
+        .iter()
+        .find(|(_, button)| {
+            button.just_released(q_windows.single(), &view, &mouse)
+        })
+        .map(|(&FaceButton(new_difficulty), _)| new_difficulty);
+    let Some(new_difficulty) = clicked_difficulty.or(key_difficulty) else {
+        return;
+    };
+    next_agent_state.set(AgentState::Resting);
+    commands.remove_resource::<Playback>();
+    if new_difficulty != **difficulty {
+        next_difficulty.set(new_difficulty);
+        next_app_state.set(GameState::Playing);
+        cursor.reset();
+        replay.clear();
+    } else {
+        // holding shift deals the same board again
+        let same_seed =
+            keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        restart(
+            q_board,
+            next_app_state,
+            app_state,
+            stats,
+            cursor,
+            replay,
+            same_seed,
+        );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn check_player_action(
     mouse: Res<Input<MouseButton>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<CameraView>,
     mut q_board: Query<&mut Board>,
     mut next_app_state: ResMut<NextState<GameState>>,
-    mut q_record: Query<&mut Record>,
+    mut stats: ResMut<Stats>,
     ui_sizing: Res<UISizing>,
+    paused: Res<Paused>,
+    auto_flag: Res<AutoFlag>,
+    mut replay: ResMut<Replay>,
+    safe_chord: Res<SafeChord>,
+    mut tile_changed: EventWriter<TileChanged>,
 ) {
+    if paused.0 {
+        return;
+    }
     let mut board = q_board.single_mut();
-    let mut record = q_record.single_mut();
-    if let Some(position) = q_windows.single().cursor_position() {
-        let action_type = if mouse.just_released(MouseButton::Left) {
+    if let Some(position) = view.cursor_position(q_windows.single()) {
+        let left = MouseButton::Left;
+        let right = MouseButton::Right;
+        let on_number =
+            ui_sizing.clicked_tile_pos(position).is_some_and(|pos| {
+                matches!(board.tile_state(pos), TileState::UncoveredSafe(_))
+            });
+        // chord with the middle button, unless it dragged the view, by
+        // releasing left and right together, or by left clicking a number
+        let chording = (mouse.just_released(MouseButton::Middle)
+            && !view.dragged())
+            || (mouse.just_released(left) && mouse.pressed(right))
+            || (mouse.just_released(right) && mouse.pressed(left))
+            || (mouse.just_released(left) && on_number);
+        if chording {
+            if let Some(pos) =
+                ui_sizing.clicked_tile_pos(position).filter(|&pos| {
+                    !safe_chord.0 || agent::flags_are_deduced(&board, pos)
+                })
+            {
+                chord(
+                    &mut board,
+                    pos,
+                    &mut next_app_state,
+                    &mut stats,
+                    &mut replay,
+                    &mut tile_changed,
+                );
+                if auto_flag.0 {
+                    flag_forced_bombs(
+                        &mut board,
+                        &mut next_app_state,
+                        &mut stats,
+                        &mut replay,
+                        &mut tile_changed,
+                    );
+                }
+            }
+            return;
+        }
+        let action_type = if mouse.just_released(left) && !mouse.pressed(right)
+        {
             Some(ActionType::Uncover)
-        } else if mouse.just_pressed(MouseButton::Right) {
+        } else if mouse.just_pressed(right) && !mouse.pressed(left) {
             Some(ActionType::Flag)
         } else {
             None
         };
         if let Some(action_type) = action_type {
-            // this ensures we can't click slightly above the first row/col
+            // this ensures we can't click slightly above the first row/col,
+            // and numbers can only be chorded
             if let Some(pos) = ui_sizing.clicked_tile_pos(position) {
-                if !matches!(board.tile_state(pos), TileState::UncoveredSafe(_))
-                {
+                if !on_number {
                     let action = Action { pos, action_type };
-                    complete_action(
+                    let result = complete_action(
                         &mut board,
                         action,
                         &mut next_app_state,
-                        &mut record,
+                        &mut stats,
+                        &mut replay,
+                        &mut tile_changed,
                     );
+                    if auto_flag.0
+                        && action_type == ActionType::Uncover
+                        && result == ActionResult::Continue
+                    {
+                        flag_forced_bombs(
+                            &mut board,
+                            &mut next_app_state,
+                            &mut stats,
+                            &mut replay,
+                            &mut tile_changed,
+                        );
+                    }
                 }
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn check_keyboard_action(
+    keys: Res<Input<KeyCode>>,
+    mut q_board: Query<&mut Board>,
+    mut cursor: ResMut<Cursor>,
+    mut next_app_state: ResMut<NextState<GameState>>,
+    mut stats: ResMut<Stats>,
+    paused: Res<Paused>,
+    auto_flag: Res<AutoFlag>,
+    mut replay: ResMut<Replay>,
+    mut tile_changed: EventWriter<TileChanged>,
+) {
+    if paused.0 {
+        return;
+    }
+    let mut board = q_board.single_mut();
+    let step = if keys.just_pressed(KeyCode::Left) {
+        Some((-1, 0))
+    } else if keys.just_pressed(KeyCode::Right) {
+        Some((1, 0))
+    } else if keys.just_pressed(KeyCode::Up) {
+        Some((0, -1))
+    } else if keys.just_pressed(KeyCode::Down) {
+        Some((0, 1))
+    } else {
+        None
+    };
+    if let Some(step) = step {
+        cursor.step(&board, step);
+        return;
+    }
+    let action_type = if keys.just_pressed(KeyCode::Space) {
+        ActionType::Uncover
+    } else if keys.just_pressed(KeyCode::F) {
+        ActionType::Flag
+    } else {
+        return;
+    };
+    // the first key press only shows the cursor
+    let Some(pos) = cursor.pos() else {
+        cursor.step(&board, (0, 0));
+        return;
+    };
+    if !matches!(board.tile_state(pos), TileState::UncoveredSafe(_)) {
+        let action = Action { pos, action_type };
+        let result = complete_action(
+            &mut board,
+            action,
+            &mut next_app_state,
+            &mut stats,
+            &mut replay,
+            &mut tile_changed,
+        );
+        if auto_flag.0
+            && action_type == ActionType::Uncover
+            && result == ActionResult::Continue
+        {
+            flag_forced_bombs(
+                &mut board,
+                &mut next_app_state,
+                &mut stats,
+                &mut replay,
+                &mut tile_changed,
+            );
+        }
+    }
+}
+
+/// Enter uncovers every remaining tile once each bomb is correctly flagged
+pub fn check_finish(
+    keys: Res<Input<KeyCode>>,
+    mut q_board: Query<&mut Board>,
+    mut next_app_state: ResMut<NextState<GameState>>,
+    mut stats: ResMut<Stats>,
+    paused: Res<Paused>,
+    mut replay: ResMut<Replay>,
+    mut tile_changed: EventWriter<TileChanged>,
+) {
+    if paused.0 || !keys.just_pressed(KeyCode::Return) {
+        return;
+    }
+    let mut board = q_board.single_mut();
+    // a spare flag on a safe tile would leave the counter below zero
+    if board.num_bombs_left() != 0 || board.true_bombs_remaining() != 0 {
+        return;
+    }
+    let covered = board
+        .tiles()
+        .filter(|(_, state)| state.covered())
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    for pos in covered {
+        let result = complete_action(
+            &mut board,
+            Action::uncover(pos),
+            &mut next_app_state,
+            &mut stats,
+            &mut replay,
+            &mut tile_changed,
+        );
+        if result != ActionResult::Continue {
+            return;
+        }
+    }
+}
+
+// only ever flags, unlike the bot which also uncovers and guesses
+fn flag_forced_bombs(
+    board: &mut Board,
+    next_app_state: &mut ResMut<NextState<GameState>>,
+    stats: &mut Stats,
+    replay: &mut Replay,
+    tile_changed: &mut EventWriter<TileChanged>,
+) {
+    let flags = agent::get_trivial_actions(board)
+        .into_iter()
+        .filter(|action| {
+            action.action_type == ActionType::Flag
+                // flagging a question mark would clear it instead
+                && board.tile_state(action.pos) == TileState::Covered
+        })
+        .collect::<Vec<_>>();
+    for action in flags {
+        complete_action(
+            board,
+            action,
+            next_app_state,
+            stats,
+            replay,
+            tile_changed,
+        );
+    }
+}
+
+fn chord(
+    board: &mut Board,
+    pos: TilePos,
+    next_app_state: &mut ResMut<NextState<GameState>>,
+    stats: &mut Stats,
+    replay: &mut Replay,
+    tile_changed: &mut EventWriter<TileChanged>,
+) {
+    // only chord on numbers that already have enough flags around them
+    for neighbour in board.chord_preview(pos).unwrap_or_default() {
+        let action = Action::uncover(neighbour);
+        let result = complete_action(
+            board,
+            action,
+            next_app_state,
+            stats,
+            replay,
+            tile_changed,
+        );
+        if result != ActionResult::Continue {
+            return;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn check_bot_action(
     mut q_board: Query<&mut Board>,
     mut next_app_state: ResMut<NextState<GameState>>,
     mut next_agent_state: ResMut<NextState<AgentState>>,
     agent_state: ResMut<State<AgentState>>,
     app_state: ResMut<State<GameState>>,
-    mut q_record: Query<&mut Record>,
+    mut stats: ResMut<Stats>,
     mut q_bot_buttons: Query<(&crate::Button, &BotButton)>,
     mouse: Res<Input<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    (q_windows, view): (Query<&Window, With<PrimaryWindow>>, Res<CameraView>),
     mut q_face_buttons: Query<(&mut TextureAtlasSprite, &FaceButton)>,
+    cursor: ResMut<Cursor>,
+    paused: Res<Paused>,
+    (mut cache, style, budget, skill): (
+        Local<agent::SolverCache>,
+        Res<agent::GuessStyle>,
+        Res<agent::GuessBudget>,
+        Res<agent::BotSkill>,
+    ),
+    time: Res<Time>,
+    (mut bot_speed, mut auto_loop, mut timer): (
+        ResMut<BotSpeed>,
+        ResMut<AutoLoop>,
+        ResMut<GameTimer>,
+    ),
+    (mut replay, mut tile_changed): (ResMut<Replay>, EventWriter<TileChanged>),
 ) {
-    let mut record = q_record.single_mut();
+    // agent state is left alone so the bot carries on where it was
+    if paused.0 {
+        return;
+    }
     let window = q_windows.single();
     for (button, bot_button) in &mut q_bot_buttons {
-        if button.just_released(window, &mouse) {
+        if button.just_released(window, &view, &mouse) {
             if !matches!(app_state.get(), GameState::Playing) {
-                restart(q_board, next_app_state, app_state, q_record);
+                restart(
+                    q_board,
+                    next_app_state,
+                    app_state,
+                    stats,
+                    cursor,
+                    replay,
+                    false,
+                );
                 next_agent_state.set(bot_button.bot_effect);
                 return;
             }
@@ -121,18 +439,69 @@ pub fn check_bot_action(
         }
     }
     let mut board = q_board.single_mut();
-    if !matches!(agent_state.get(), AgentState::Resting) {
-        let actions = agent::get_all_actions(&board);
+    if matches!(agent_state.get(), AgentState::FlaggingOnly) {
+        // never uncovers, so stops once every certain bomb is flagged
+        let (_, bombs) = agent::deduction_frontier(&board);
+        let flags = bombs
+            .into_iter()
+            .filter(|&pos| board.tile_state(pos) == TileState::Covered)
+            .map(Action::flag)
+            .collect::<Vec<_>>();
+        if flags.is_empty() {
+            next_agent_state.set(AgentState::Resting);
+        }
+        for action in flags {
+            timer.mark_bot_move();
+            complete_action(
+                &mut board,
+                action,
+                &mut next_app_state,
+                &mut stats,
+                &mut replay,
+                &mut tile_changed,
+            );
+        }
+    } else if !matches!(agent_state.get(), AgentState::Resting) {
+        // pace full runs so they can be watched, single moves happen at once
+        let num_moves = match agent_state.get() {
+            AgentState::Thinking => {
+                bot_speed.timer.tick(time.delta());
+                bot_speed.timer.times_finished_this_tick() as usize
+            }
+            _ => usize::MAX,
+        };
+        if num_moves == 0 {
+            return;
+        }
+        let actions = agent::get_all_actions_cached(
+            &board,
+            &mut cache,
+            agent::FirstGuess::default(),
+            *style,
+            *budget,
+            *skill,
+        );
         if actions.is_empty() {
             next_agent_state.set(AgentState::Resting)
         }
-        for action in actions {
+        for (action, origin) in actions.into_iter().take(num_moves) {
+            if origin == agent::ActionOrigin::Guessed {
+                stats.record_mut(board.difficulty()).total_guesses += 1;
+            }
+            timer.mark_bot_move();
             let result = complete_action(
                 &mut board,
                 action,
                 &mut next_app_state,
-                &mut record,
+                &mut stats,
+                &mut replay,
+                &mut tile_changed,
             );
+            if result != ActionResult::Continue
+                && matches!(agent_state.get(), AgentState::Thinking)
+            {
+                auto_loop.schedule_restart();
+            }
             if result != ActionResult::Continue
                 || matches!(agent_state.get(), AgentState::ThinkingOneMoveOnly)
             {
@@ -146,41 +515,74 @@ pub fn check_bot_action(
     }
 }
 
-pub fn end_game(record: &mut Record, result: &ActionResult, board: &Board) {
-    match result {
-        ActionResult::Win => {
-            record.win += 1;
-            println!("You won!");
-        }
-        ActionResult::Lose => {
-            record.loss += 1;
-            println!("You lost");
+pub fn check_undo_redo(
+    keys: Res<Input<KeyCode>>,
+    mut q_board: Query<&mut Board>,
+    mut next_app_state: ResMut<NextState<GameState>>,
+    paused: Res<Paused>,
+    mut replay: ResMut<Replay>,
+    mut stats: ResMut<Stats>,
+) {
+    if paused.0 {
+        return;
+    }
+    let mut board = q_board.single_mut();
+    let changed = if keys.just_pressed(KeyCode::U) && replay.can_undo() {
+        // the game was counted when it ended, so it's taken off again
+        let result = board.result();
+        if result != ActionResult::Continue {
+            stats.remove_game(&result, &board);
         }
-        ActionResult::Continue => {
-            record.dnf += 1;
-            println!("You didn't finish the game...");
+        replay.undo(&mut board)
+    } else if keys.just_pressed(KeyCode::R) && replay.redo(&mut board) {
+        let result = board.result();
+        if result != ActionResult::Continue {
+            end_game(&mut stats, &result, &board);
         }
+        true
+    } else {
+        false
+    };
+    // undoing can bring a finished game back into play and redoing can end it
+    if changed {
+        next_app_state.set(match board.result() {
+            ActionResult::Win => GameState::Won,
+            ActionResult::Lose => GameState::Lost,
+            ActionResult::Continue => GameState::Playing,
+        });
+    }
+}
+
+pub fn end_game(stats: &mut Stats, result: &ActionResult, board: &Board) {
+    stats.add_game(result, board);
+    let record = stats.record(board.difficulty()).unwrap();
+    match result {
+        ActionResult::Win => println!("You won!"),
+        ActionResult::Lose => println!("You lost"),
+        ActionResult::Continue => println!("You didn't finish the game..."),
     }
-    record.total_bombs_cleared +=
-        board.num_bombs_total() - board.num_bombs_left() as usize;
-    record.total_bombs += board.num_bombs_total();
     println!("Record: {}\n", record);
 }
 
-fn complete_action(
+pub fn complete_action(
     board: &mut Board,
     action: Action,
     next_app_state: &mut ResMut<NextState<GameState>>,
-    record: &mut Record,
+    stats: &mut Stats,
+    replay: &mut Replay,
+    tile_changed: &mut EventWriter<TileChanged>,
 ) -> ActionResult {
-    let result = board.apply_action(action);
+    let result = replay.apply(board, action);
+    for &(pos, from, to) in board.last_changes() {
+        tile_changed.send(TileChanged { pos, from, to });
+    }
     match result {
         ActionResult::Win => {
-            end_game(record, &result, board);
+            end_game(stats, &result, board);
             next_app_state.set(GameState::Won);
         }
         ActionResult::Lose => {
-            end_game(record, &result, board);
+            end_game(stats, &result, board);
             next_app_state.set(GameState::Lost);
         }
         ActionResult::Continue => {}
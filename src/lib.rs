@@ -1,11 +1,26 @@
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
 use bevy::window::PrimaryWindow;
 use bevy::{prelude::*, window::close_on_esc};
+use clap::builder::PossibleValue;
 use instant::Instant;
 use itertools::Itertools;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Write};
+use std::iter::Copied;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::path::PathBuf;
 use std::slice::Iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 // redirect println! to console.log in wasm
 #[cfg(target_family = "wasm")]
@@ -25,35 +40,136 @@ custom_print::define_macros!({ cprintln }, concat, unsafe fn (crate::log)(&str))
 macro_rules! println { ($($args:tt)*) => { cprintln!($($args)*); } }
 
 mod actions;
-mod board;
+pub mod board;
+pub mod replay;
 pub mod setup;
+/// Plays in the terminal instead of a window
+#[cfg(not(target_family = "wasm"))]
+pub mod tui;
 
-use actions::{agent, *};
+/// The only solver, shared by the bot buttons and simulations
+pub use actions::agent;
+use actions::*;
+use agent::{
+    ActionOrigin, BotSkill, FirstGuess, GuessBudget, GuessStyle, SolverCache,
+    SolverTimings,
+};
 use board::*;
-use setup::{resize, setup, UISizing};
+use replay::Replay;
+use setup::{relayout, resize, setup, UISizing};
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<GameState>()
+        #[cfg(all(feature = "debug-reveal", debug_assertions))]
+        app.add_systems(Last, reveal_bombs.after(sync_board_with_tile_sprites));
+        #[cfg(feature = "serde")]
+        app.init_resource::<ReplayPath>()
+            .add_systems(Update, save_replay)
+            .add_systems(Startup, load_stats)
+            .add_systems(Last, save_stats);
+        app.init_resource::<GameTimer>()
+            .init_resource::<Cursor>()
+            .init_resource::<CameraView>()
+            .init_resource::<Paused>()
+            .init_resource::<AutoFlag>()
+            .init_resource::<AutoLoop>()
+            .init_resource::<StrictFlags>()
+            .init_resource::<FirstClick>()
+            .init_resource::<BoardTopology>()
+            .init_resource::<BoardAdjacency>()
+            .init_resource::<BoardWinCondition>()
+            .init_resource::<SafeChord>()
+            .init_resource::<LossReveal>()
+            .init_resource::<BotSpeed>()
+            .init_resource::<MuteAudio>()
+            .init_resource::<ShowHeatmap>()
+            .init_resource::<AssistOverlay>()
+            .init_resource::<BoardOdds>()
+            .init_resource::<Stats>()
+            .init_resource::<Replay>()
+            .init_resource::<GuessBudget>()
+            .init_resource::<GuessStyle>()
+            .init_resource::<BotSkill>()
+            .init_resource::<setup::Theme>()
+            .add_event::<TileChanged>()
+            .add_state::<GameState>()
             .add_state::<AgentState>()
             .add_state::<Difficulty>()
             .add_systems(Startup, setup)
             .add_systems(First, (update_bot_buttons, update_face_buttons))
-            .add_systems(Update, (check_bot_action, close_on_esc))
             .add_systems(
                 Update,
-                check_player_action.run_if(
-                    in_state(GameState::Playing)
-                        .and_then(in_state(AgentState::Resting)),
+                (
+                    check_bot_action,
+                    close_on_esc,
+                    toggle_pause,
+                    toggle_auto_flag,
+                    toggle_auto_loop,
+                    zoom_and_pan,
+                    update_bot_speed,
+                    toggle_mute,
+                    toggle_heatmap,
+                    toggle_assist_overlay,
+                    update_board_odds,
                 ),
             )
-            .add_systems(PostUpdate, check_restart)
+            .add_systems(
+                Update,
+                (update_hover_tip, update_danger_meter, check_hint)
+                    .after(update_board_odds),
+            )
+            .add_systems(
+                Update,
+                play_replay.run_if(resource_exists::<Playback>()),
+            )
+            .add_systems(
+                Update,
+                (check_player_action, check_keyboard_action, check_finish)
+                    .run_if(
+                        in_state(GameState::Playing)
+                            .and_then(in_state(AgentState::Resting))
+                            .and_then(not(resource_exists::<Playback>())),
+                    ),
+            )
+            .add_systems(
+                Update,
+                check_undo_redo.run_if(
+                    in_state(AgentState::Resting)
+                        .and_then(not(resource_exists::<Playback>())),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Won),
+                record_best_time.run_if(not(resource_exists::<Playback>())),
+            )
+            .add_systems(
+                Update,
+                animate_loss.run_if(resource_exists::<LossAnimation>()),
+            )
+            .add_systems(OnExit(GameState::Lost), stop_loss_animation)
+            .add_systems(OnEnter(GameState::Playing), cancel_auto_loop)
+            .add_systems(
+                PostUpdate,
+                (check_restart, check_auto_loop, play_sounds),
+            )
             .add_systems(PostUpdate, resize.after(check_restart))
+            .add_systems(PostUpdate, relayout.after(resize))
             .add_systems(
                 Last,
-                (sync_board_with_tile_sprites, sync_bomb_counter),
+                start_loss_animation.before(sync_board_with_tile_sprites),
+            )
+            .add_systems(
+                Last,
+                (
+                    sync_board_with_tile_sprites,
+                    sync_bomb_counter,
+                    sync_safe_tiles_counter,
+                    sync_timer,
+                    sync_best_time,
+                    sync_loop_indicator,
+                ),
             );
     }
 }
@@ -72,25 +188,63 @@ pub enum AgentState {
     Resting,
     Thinking,
     ThinkingOneMoveOnly,
+    FlaggingOnly,
 }
 
-#[derive(
-    States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default, clap::ValueEnum,
-)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Difficulty {
     Easy,
     Medium,
     #[default]
     Hard,
+    Custom {
+        width: usize,
+        height: usize,
+        bombs: usize,
+    },
 }
 
 impl Display for Difficulty {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(format!("{:?}", self).as_ref())
+        match self {
+            Difficulty::Custom {
+                width,
+                height,
+                bombs,
+            } => write!(f, "Custom ({width}x{height}, {bombs} bombs)"),
+            _ => f.write_str(format!("{:?}", self).as_ref()),
+        }
+    }
+}
+
+// can't derive these with a data-carrying variant, so only the standard
+// difficulties are listed: custom boards are built from their own CLI flags
+impl States for Difficulty {
+    type Iter = Copied<Iter<'static, Difficulty>>;
+
+    fn variants() -> Self::Iter {
+        Difficulty::iter().copied()
+    }
+}
+
+impl clap::ValueEnum for Difficulty {
+    fn value_variants<'a>() -> &'a [Self] {
+        Difficulty::iter().as_slice()
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        match self {
+            Difficulty::Easy => Some(PossibleValue::new("easy")),
+            Difficulty::Medium => Some(PossibleValue::new("medium")),
+            Difficulty::Hard => Some(PossibleValue::new("hard")),
+            Difficulty::Custom { .. } => None,
+        }
     }
 }
 
 impl Difficulty {
+    /// Standard difficulties only, `Custom` boards aren't enumerable
     pub fn iter() -> Iter<'static, Difficulty> {
         static VALS: [Difficulty; 3] =
             [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
@@ -102,6 +256,7 @@ impl Difficulty {
             Difficulty::Easy => 10,
             Difficulty::Medium => 40,
             Difficulty::Hard => 99,
+            Difficulty::Custom { bombs, .. } => *bombs,
         }
     }
 
@@ -110,17 +265,225 @@ impl Difficulty {
             Difficulty::Easy => (10, 10),
             Difficulty::Medium => (16, 16),
             Difficulty::Hard => (30, 16),
+            Difficulty::Custom { width, height, .. } => (*width, *height),
+        }
+    }
+
+    /// Share of the tiles that are bombs
+    ///
+    /// ```
+    /// use minesweeper::Difficulty;
+    /// assert_eq!(Difficulty::Easy.bomb_density(), 0.1);
+    /// ```
+    pub fn bomb_density(&self) -> f64 {
+        let (width, height) = self.grid_size();
+        self.num_bombs() as f64 / (width * height) as f64
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Stats {
+    records: HashMap<Difficulty, Record>,
+    best_times: HashMap<Difficulty, f32>,
+    // the best time the last game's win replaced, so undoing the win can put
+    // it back
+    replaced_time: Option<(Difficulty, Option<f32>)>,
+}
+
+impl Stats {
+    pub fn record(&self, difficulty: Difficulty) -> Option<&Record> {
+        self.records.get(&difficulty)
+    }
+
+    /// Fastest win in seconds
+    pub fn best_time(&self, difficulty: Difficulty) -> Option<f32> {
+        self.best_times.get(&difficulty).copied()
+    }
+
+    fn record_time(&mut self, difficulty: Difficulty, time: f32) {
+        let best = self.best_time(difficulty);
+        self.replaced_time = Some((difficulty, best));
+        self.best_times
+            .insert(difficulty, best.map_or(time, |best| best.min(time)));
+    }
+
+    pub fn add_game(&mut self, result: &ActionResult, board: &Board) {
+        self.replaced_time = None;
+        self.record_mut(board.difficulty()).add_game(result, board);
+    }
+
+    /// Takes the last game back off the record, along with any best time it
+    /// set, for when its final move is undone. `board` is as it ended.
+    pub fn remove_game(&mut self, result: &ActionResult, board: &Board) {
+        let difficulty = board.difficulty();
+        self.record_mut(difficulty).remove_game(result, board);
+        if let Some((replaced, best)) = self.replaced_time.take() {
+            if replaced == difficulty {
+                match best {
+                    Some(best) => self.best_times.insert(difficulty, best),
+                    None => self.best_times.remove(&difficulty),
+                };
+            }
+        }
+    }
+
+    fn record_mut(&mut self, difficulty: Difficulty) -> &mut Record {
+        self.records
+            .entry(difficulty)
+            .or_insert_with(|| Record::new(difficulty))
+    }
+}
+
+// JSON only has string keys, and custom difficulties don't make good ones
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedStats {
+    records: Vec<Record>,
+    best_times: Vec<(Difficulty, f32)>,
+}
+
+#[cfg(feature = "serde")]
+impl Stats {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&SavedStats {
+            records: self.records.values().cloned().collect(),
+            best_times: self.best_times.clone().into_iter().collect(),
+        })
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Stats> {
+        let saved: SavedStats = serde_json::from_str(json)?;
+        Ok(Stats {
+            records: saved
+                .records
+                .into_iter()
+                .map(|record| (record.difficulty, record))
+                .collect(),
+            best_times: saved.best_times.into_iter().collect(),
+            ..default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
         }
+        std::fs::write(path, self.to_json()?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Stats> {
+        Ok(Stats::from_json(&std::fs::read_to_string(path)?)?)
     }
 }
 
-#[derive(Component, Debug, Default)]
+// where the `dirs` crate would put per-user files on each platform, given
+// the XDG variable and fallback under home. Windows and macOS keep config and
+// data together.
+#[cfg(all(feature = "serde", not(target_family = "wasm")))]
+fn user_dir(xdg_var: &str, xdg_fallback: &str) -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os(xdg_var)
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(xdg_fallback)))
+    };
+    Some(dir?.join("minesweeper"))
+}
+
+#[cfg(all(feature = "serde", not(target_family = "wasm")))]
+fn stats_path() -> Option<PathBuf> {
+    Some(user_dir("XDG_CONFIG_HOME", ".config")?.join("stats.json"))
+}
+
+#[cfg(all(feature = "serde", not(target_family = "wasm")))]
+fn read_saved_stats() -> Option<String> {
+    std::fs::read_to_string(stats_path()?).ok()
+}
+
+#[cfg(all(feature = "serde", not(target_family = "wasm")))]
+fn write_saved_stats(stats: &Stats) -> io::Result<()> {
+    match stats_path() {
+        Some(path) => stats.save(&path),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no config directory",
+        )),
+    }
+}
+
+#[cfg(all(feature = "serde", target_family = "wasm"))]
+const STATS_KEY: &str = "minesweeper-stats";
+
+#[cfg(all(feature = "serde", target_family = "wasm"))]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(all(feature = "serde", target_family = "wasm"))]
+fn read_saved_stats() -> Option<String> {
+    local_storage()?.get_item(STATS_KEY).ok()?
+}
+
+#[cfg(all(feature = "serde", target_family = "wasm"))]
+fn write_saved_stats(stats: &Stats) -> io::Result<()> {
+    let storage = local_storage().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no local storage")
+    })?;
+    storage
+        .set_item(STATS_KEY, &stats.to_json()?)
+        .map_err(|_| io::Error::other("couldn't write to local storage"))
+}
+
+// a missing or unreadable file just means starting afresh
+#[cfg(feature = "serde")]
+fn load_stats(mut stats: ResMut<Stats>) {
+    if let Some(json) = read_saved_stats() {
+        match Stats::from_json(&json) {
+            Ok(saved) => *stats = saved,
+            Err(error) => warn!("Ignoring saved stats: {error}"),
+        }
+    }
+}
+
+// saved as soon as a game is counted, so a crash or a closed browser tab
+// loses nothing. Systems that only might change the stats still mark them
+// changed, so the JSON is compared to what was last saved.
+#[cfg(feature = "serde")]
+fn save_stats(stats: Res<Stats>, mut saved: Local<Option<String>>) {
+    if !stats.is_changed() {
+        return;
+    }
+    let json = match stats.to_json() {
+        Ok(json) => json,
+        Err(error) => return warn!("Couldn't save stats: {error}"),
+    };
+    // the stats were just loaded, so they're already saved
+    if stats.is_added() {
+        *saved = Some(json);
+        return;
+    }
+    if saved.as_ref() == Some(&json) {
+        return;
+    }
+    match write_saved_stats(&stats) {
+        Ok(()) => *saved = Some(json),
+        Err(error) => warn!("Couldn't save stats: {error}"),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Record {
     win: usize,
     loss: usize,
     dnf: usize,
     total_bombs_cleared: usize,
     total_bombs: usize,
+    total_guesses: usize,
     difficulty: Difficulty,
 }
 
@@ -131,25 +494,83 @@ impl Record {
             ..default()
         }
     }
+
+    fn add_game(&mut self, result: &ActionResult, board: &Board) {
+        match result {
+            ActionResult::Win => self.win += 1,
+            ActionResult::Lose => self.loss += 1,
+            ActionResult::Continue => self.dnf += 1,
+        }
+        self.total_bombs_cleared +=
+            board.num_bombs_total() - board.num_bombs_left() as usize;
+        self.total_bombs += board.num_bombs_total();
+    }
+
+    fn remove_game(&mut self, result: &ActionResult, board: &Board) {
+        let count = match result {
+            ActionResult::Win => &mut self.win,
+            ActionResult::Lose => &mut self.loss,
+            ActionResult::Continue => &mut self.dnf,
+        };
+        *count = count.saturating_sub(1);
+        self.total_bombs_cleared = self.total_bombs_cleared.saturating_sub(
+            board.num_bombs_total() - board.num_bombs_left() as usize,
+        );
+        self.total_bombs =
+            self.total_bombs.saturating_sub(board.num_bombs_total());
+    }
+
+    fn merge(&mut self, other: &Record) {
+        self.win += other.win;
+        self.loss += other.loss;
+        self.dnf += other.dnf;
+        self.total_bombs_cleared += other.total_bombs_cleared;
+        self.total_bombs += other.total_bombs;
+        self.total_guesses += other.total_guesses;
+    }
+
     fn win_rate(&self) -> f64 {
         self.win as f64 / (self.win + self.loss + self.dnf) as f64
     }
 
+    /// Wilson score interval for the win rate, `z` standard deviations wide
+    pub fn win_rate_interval(&self, z: f64) -> (f64, f64) {
+        let n = (self.win + self.loss + self.dnf) as f64;
+        if n == 0.0 {
+            return (0.0, 1.0);
+        }
+        let p = self.win as f64 / n;
+        let denominator = 1.0 + z * z / n;
+        let centre = (p + z * z / (2.0 * n)) / denominator;
+        let half_width = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt()
+            / denominator;
+        (
+            (centre - half_width).max(0.0),
+            (centre + half_width).min(1.0),
+        )
+    }
+
     fn clearance_rate(&self) -> f64 {
         self.total_bombs_cleared as f64 / self.total_bombs as f64
     }
+
+    fn guesses_per_game(&self) -> f64 {
+        self.total_guesses as f64 / (self.win + self.loss + self.dnf) as f64
+    }
 }
 
 impl Display for Record {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let string = format!(
-            "{}-{}-{} on {:?} ({:.2}% win rate, {:.2}% bombs cleared)",
+            "{}-{}-{} on {} ({:.2}% win rate, {:.2}% bombs cleared, {:.2} \
+             guesses per game)",
             self.win,
             self.loss,
             self.dnf,
             self.difficulty,
             100.0 * self.win_rate(),
             100.0 * self.clearance_rate(),
+            self.guesses_per_game(),
         );
         f.write_str(string.as_ref())
     }
@@ -164,21 +585,23 @@ impl Button {
     fn pressed(
         &self,
         window: &Window,
+        view: &CameraView,
         mouse: &Res<Input<MouseButton>>,
     ) -> bool {
-        mouse.pressed(MouseButton::Left) && self.mouse_over(window)
+        mouse.pressed(MouseButton::Left) && self.mouse_over(window, view)
     }
 
     fn just_released(
         &self,
         window: &Window,
+        view: &CameraView,
         mouse: &Res<Input<MouseButton>>,
     ) -> bool {
-        mouse.just_released(MouseButton::Left) && self.mouse_over(window)
+        mouse.just_released(MouseButton::Left) && self.mouse_over(window, view)
     }
 
-    fn mouse_over(&self, window: &Window) -> bool {
-        if let Some(mouse_from_corner) = window.cursor_position() {
+    fn mouse_over(&self, window: &Window, view: &CameraView) -> bool {
+        if let Some(mouse_from_corner) = view.cursor_position(window) {
             let centre = Vec2::new(window.width(), window.height()) / 2.0;
             let mouse_pos = (mouse_from_corner - centre) * Vec2::new(1.0, -1.0);
             if self.location.contains(mouse_pos) {
@@ -206,7 +629,7 @@ impl FaceButton {
             .find_position(|x| **x == difficulty)
             .unwrap()
             .0
-            * 5;
+            * 6;
         offset
             + match state {
                 FaceButtonState::Unpressed => 0,
@@ -214,6 +637,7 @@ impl FaceButton {
                 FaceButtonState::Playing => 2,
                 FaceButtonState::Win => 3,
                 FaceButtonState::Loss => 4,
+                FaceButtonState::Paused => 5,
             }
     }
 }
@@ -225,16 +649,18 @@ pub enum FaceButtonState {
     Playing,
     Win,
     Loss,
+    Paused,
 }
 
 fn update_bot_buttons(
     mut q_buttons: Query<(&mut TextureAtlasSprite, &Button, &BotButton)>,
     mouse: Res<Input<MouseButton>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<CameraView>,
 ) {
     for (mut sprite, button, bot_button) in q_buttons.iter_mut() {
         sprite.index = bot_button.unpressed_index;
-        if button.pressed(q_windows.single(), &mouse) {
+        if button.pressed(q_windows.single(), &view, &mouse) {
             sprite.index = bot_button.pressed_index;
         }
     }
@@ -243,24 +669,28 @@ fn update_bot_buttons(
 fn update_face_buttons(
     mut q_face_buttons: Query<(&mut TextureAtlasSprite, &Button, &FaceButton)>,
     app_state: ResMut<State<GameState>>,
+    paused: Res<Paused>,
     mouse: Res<Input<MouseButton>>,
     q_windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<CameraView>,
 ) {
     for (mut sprite, button, face_button) in q_face_buttons.iter_mut() {
-        let face_button_state = if button.pressed(q_windows.single(), &mouse) {
-            FaceButtonState::Pressed
-        } else {
-            match app_state.get() {
-                GameState::Won => FaceButtonState::Win,
-                GameState::Lost => FaceButtonState::Loss,
-                GameState::Playing => FaceButtonState::Unpressed,
-            }
-        };
+        let face_button_state =
+            if button.pressed(q_windows.single(), &view, &mouse) {
+                FaceButtonState::Pressed
+            } else {
+                match app_state.get() {
+                    GameState::Won => FaceButtonState::Win,
+                    GameState::Lost => FaceButtonState::Loss,
+                    GameState::Playing if paused.0 => FaceButtonState::Paused,
+                    GameState::Playing => FaceButtonState::Unpressed,
+                }
+            };
         sprite.index = face_button.sheet_index(face_button_state)
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy)]
 pub struct BombCounterDigit;
 
 impl BombCounterDigit {
@@ -291,33 +721,1071 @@ fn sync_bomb_counter(
     }
 }
 
+/// Shows how many safe tiles are left to uncover
+#[derive(Component, Clone, Copy)]
+pub struct SafeTilesDigit;
+
+fn sync_safe_tiles_counter(
+    q_board: Query<&Board>,
+    mut q_digits: Query<(&mut TextureAtlasSprite, &SafeTilesDigit)>,
+) {
+    if let Ok(board) = q_board.get_single() {
+        // only room for three digits
+        format!("{:03}", board.safe_tiles_remaining().min(999))
+            .chars()
+            .map(BombCounterDigit::sheet_index)
+            .zip(q_digits.iter_mut())
+            .for_each(|(index, (mut sprite, _))| {
+                sprite.index = index;
+            });
+    }
+}
+
+/// Toggled with P, stops the bot, player input and the timer. Esc still closes
+/// the window while paused since `close_on_esc` doesn't know about it.
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+fn toggle_pause(keys: Res<Input<KeyCode>>, mut paused: ResMut<Paused>) {
+    if keys.just_pressed(KeyCode::P) {
+        paused.0 = !paused.0;
+    }
+}
+
+#[derive(Resource)]
+pub struct Sounds {
+    win: Handle<AudioSource>,
+    lose: Handle<AudioSource>,
+    click: Handle<AudioSource>,
+}
+
+/// Toggled with M
+#[derive(Resource, Default)]
+pub struct MuteAudio(pub bool);
+
+fn toggle_mute(keys: Res<Input<KeyCode>>, mut mute: ResMut<MuteAudio>) {
+    if keys.just_pressed(KeyCode::M) {
+        mute.0 = !mute.0;
+    }
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    mute: Res<MuteAudio>,
+    app_state: Res<State<GameState>>,
+    q_board: Query<&Board>,
+    mut num_uncovered: Local<usize>,
+) {
+    let mut sound = None;
+    if let Ok(board) = q_board.get_single() {
+        // click whenever the player or bot uncovers something new
+        let uncovered = board
+            .tile_states()
+            .iter()
+            .filter(|state| matches!(state, TileState::UncoveredSafe(_)))
+            .count();
+        if uncovered > *num_uncovered
+            && matches!(app_state.get(), GameState::Playing)
+        {
+            sound = Some(&sounds.click);
+        }
+        *num_uncovered = uncovered;
+    }
+    if app_state.is_changed() {
+        match app_state.get() {
+            GameState::Won => sound = Some(&sounds.win),
+            GameState::Lost => sound = Some(&sounds.lose),
+            GameState::Playing => {}
+        }
+    }
+    if let Some(source) = sound {
+        if !mute.0 {
+            commands.spawn(AudioBundle {
+                source: source.clone(),
+                settings: PlaybackSettings::DESPAWN,
+            });
+        }
+    }
+}
+
+/// How fast the bot plays when left thinking, changed with + and -
+#[derive(Resource)]
+pub struct BotSpeed {
+    moves_per_second: f32,
+    timer: Timer,
+}
+
+impl BotSpeed {
+    const MIN: f32 = 1.0;
+    const MAX: f32 = 1000.0;
+
+    fn new(moves_per_second: f32) -> Self {
+        let moves_per_second = moves_per_second.clamp(Self::MIN, Self::MAX);
+        BotSpeed {
+            moves_per_second,
+            timer: Timer::from_seconds(
+                1.0 / moves_per_second,
+                TimerMode::Repeating,
+            ),
+        }
+    }
+
+    pub fn moves_per_second(&self) -> f32 {
+        self.moves_per_second
+    }
+}
+
+impl Default for BotSpeed {
+    fn default() -> Self {
+        BotSpeed::new(10.0)
+    }
+}
+
+fn update_bot_speed(
+    keys: Res<Input<KeyCode>>,
+    mut bot_speed: ResMut<BotSpeed>,
+) {
+    let faster = keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]);
+    let slower =
+        keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]);
+    let factor = match (faster, slower) {
+        (true, false) => 2.0,
+        (false, true) => 0.5,
+        _ => return,
+    };
+    *bot_speed = BotSpeed::new(bot_speed.moves_per_second * factor);
+    println!("Bot speed: {} moves/sec", bot_speed.moves_per_second);
+}
+
+/// Where S saves the current game: set from the command line, otherwise in
+/// the platform's data directory
+#[cfg(feature = "serde")]
+#[derive(Resource)]
+pub struct ReplayPath(pub Option<PathBuf>);
+
+#[cfg(feature = "serde")]
+impl Default for ReplayPath {
+    fn default() -> Self {
+        #[cfg(not(target_family = "wasm"))]
+        let path = user_dir("XDG_DATA_HOME", ".local/share")
+            .map(|dir| dir.join("replay.json"));
+        // no file system to save to
+        #[cfg(target_family = "wasm")]
+        let path = None;
+        ReplayPath(path)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn save_replay(
+    keys: Res<Input<KeyCode>>,
+    replay: Res<Replay>,
+    path: Res<ReplayPath>,
+) {
+    if keys.just_pressed(KeyCode::S) {
+        let Some(path) = &path.0 else {
+            return eprintln!("Nowhere to save the replay");
+        };
+        match replay.save(path) {
+            Ok(()) => println!("Saved replay to {}", path.display()),
+            Err(error) => eprintln!("Couldn't save replay: {error}"),
+        }
+    }
+}
+
+/// A saved game being played back, one move every `delay`. Restarting hands
+/// the board back to the player.
+#[derive(Resource)]
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+    started: bool,
+    timer: Timer,
+}
+
+impl Playback {
+    pub fn new(replay: Replay, delay: Duration) -> Self {
+        Playback {
+            replay,
+            next: 0,
+            started: false,
+            timer: Timer::new(delay, TimerMode::Repeating),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_replay(
+    mut playback: ResMut<Playback>,
+    mut q_board: Query<&mut Board>,
+    difficulty: Res<State<Difficulty>>,
+    mut next_difficulty: ResMut<NextState<Difficulty>>,
+    mut next_app_state: ResMut<NextState<GameState>>,
+    mut stats: ResMut<Stats>,
+    mut replay: ResMut<Replay>,
+    paused: Res<Paused>,
+    time: Res<Time>,
+    mut tile_changed: EventWriter<TileChanged>,
+) {
+    if paused.0 {
+        return;
+    }
+    // the board is respawned at the right size before the first move
+    if **difficulty != playback.replay.difficulty() {
+        next_difficulty.set(playback.replay.difficulty());
+        return;
+    }
+    let mut board = q_board.single_mut();
+    if !playback.started {
+        *board = playback.replay.board();
+        replay.clear();
+        playback.started = true;
+    }
+    playback.timer.tick(time.delta());
+    for _ in 0..playback.timer.times_finished_this_tick() {
+        let Some(&action) = playback.replay.actions().get(playback.next) else {
+            return;
+        };
+        playback.next += 1;
+        complete_action(
+            &mut board,
+            action,
+            &mut next_app_state,
+            &mut stats,
+            &mut replay,
+            &mut tile_changed,
+        );
+    }
+}
+
+/// Set from the command line, flagging a safe tile loses the game
+#[derive(Resource, Default)]
+pub struct StrictFlags(pub bool);
+
+/// Set from the command line, what the first click of each game keeps safe
+#[derive(Resource, Default)]
+pub struct FirstClick(pub FirstClickPolicy);
+
+/// Set from the command line, whether the edges of the board wrap around
+#[derive(Resource, Default)]
+pub struct BoardTopology(pub Topology);
+
+/// Set from the command line, which tiles count as neighbours
+#[derive(Resource, Default)]
+pub struct BoardAdjacency(pub Adjacency);
+
+/// Set from the command line, whether the bombs need flagging to win
+#[derive(Resource, Default)]
+pub struct BoardWinCondition(pub WinCondition);
+
+/// Set from the command line, a chord only goes off when the agent can deduce
+/// every flag around the number is a bomb
+#[derive(Resource, Default)]
+pub struct SafeChord(pub bool);
+
+/// Set from the command line, how long the bombs take to appear one by one
+/// after a loss. Zero shows them all at once.
+#[derive(Resource)]
+pub struct LossReveal(pub Duration);
+
+impl LossReveal {
+    pub const DEFAULT_MILLIS: u64 = 800;
+}
+
+impl Default for LossReveal {
+    fn default() -> Self {
+        LossReveal(Duration::from_millis(Self::DEFAULT_MILLIS))
+    }
+}
+
+/// Bombs the board has revealed after a loss that the sprites are still
+/// hiding, nearest the exploded bomb last so they're popped first
+#[derive(Resource)]
+pub struct LossAnimation {
+    pending: Vec<TilePos>,
+    timer: Timer,
+}
+
+impl LossAnimation {
+    // what the tile looked like before the loss
+    fn shown_state(&self, pos: TilePos, state: TileState) -> TileState {
+        match state {
+            TileState::UncoveredBomb if self.pending.contains(&pos) => {
+                TileState::Covered
+            }
+            TileState::Misflagged if self.pending.contains(&pos) => {
+                TileState::Flagged
+            }
+            _ => state,
+        }
+    }
+}
+
+// runs before the sprites are synced so no frame shows every bomb at once
+fn start_loss_animation(
+    mut commands: Commands,
+    q_board: Query<&Board>,
+    animation: Option<Res<LossAnimation>>,
+    loss_reveal: Res<LossReveal>,
+) {
+    let Ok(board) = q_board.get_single() else {
+        return;
+    };
+    if animation.is_some()
+        || loss_reveal.0.is_zero()
+        || board.result() != ActionResult::Lose
+    {
+        return;
+    }
+    let exploded = board
+        .tiles()
+        .find(|&(_, state)| state == TileState::ExplodedBomb)
+        .map_or(TilePos { col: 0, row: 0 }, |(pos, _)| pos);
+    let mut pending = board
+        .tiles()
+        .filter(|&(_, state)| {
+            matches!(state, TileState::UncoveredBomb | TileState::Misflagged)
+        })
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    pending
+        .sort_by_key(|&pos| std::cmp::Reverse(pos.squared_distance(exploded)));
+    let step = loss_reveal.0 / pending.len().max(1) as u32;
+    commands.insert_resource(LossAnimation {
+        pending,
+        timer: Timer::new(step, TimerMode::Repeating),
+    });
+}
+
+fn animate_loss(mut animation: ResMut<LossAnimation>, time: Res<Time>) {
+    animation.timer.tick(time.delta());
+    for _ in 0..animation.timer.times_finished_this_tick() {
+        animation.pending.pop();
+    }
+}
+
+// kept until now, even once finished, so each loss only animates once
+fn stop_loss_animation(mut commands: Commands) {
+    commands.remove_resource::<LossAnimation>();
+}
+
+/// Toggled with G, flags bombs that the player's last uncover made certain
+#[derive(Resource, Default)]
+pub struct AutoFlag(pub bool);
+
+fn toggle_auto_flag(
+    keys: Res<Input<KeyCode>>,
+    mut auto_flag: ResMut<AutoFlag>,
+) {
+    if keys.just_pressed(KeyCode::G) {
+        auto_flag.0 = !auto_flag.0;
+        println!("Auto-flag {}", if auto_flag.0 { "on" } else { "off" });
+    }
+}
+
+/// Toggled with L: once the bot finishes a game it was playing through, a new
+/// board is dealt after a short delay and the bot carries on
+#[derive(Resource, Default)]
+pub struct AutoLoop {
+    pub on: bool,
+    restart: Option<Timer>,
+}
+
+impl AutoLoop {
+    pub const DELAY_SECONDS: f32 = 1.5;
+
+    pub fn schedule_restart(&mut self) {
+        if self.on {
+            let delay =
+                Timer::from_seconds(Self::DELAY_SECONDS, TimerMode::Once);
+            self.restart = Some(delay);
+        }
+    }
+
+    /// Ticks any pending restart, true on the frame it's due
+    pub fn restart_due(&mut self, delta: Duration) -> bool {
+        let Some(timer) = &mut self.restart else {
+            return false;
+        };
+        if timer.tick(delta).finished() {
+            self.restart = None;
+            return true;
+        }
+        false
+    }
+}
+
+fn toggle_auto_loop(
+    keys: Res<Input<KeyCode>>,
+    mut auto_loop: ResMut<AutoLoop>,
+) {
+    if keys.just_pressed(KeyCode::L) {
+        auto_loop.on = !auto_loop.on;
+        auto_loop.restart = None;
+        println!("Auto-loop {}", if auto_loop.on { "on" } else { "off" });
+    }
+}
+
+// a restart by hand goes ahead of the pending one
+fn cancel_auto_loop(mut auto_loop: ResMut<AutoLoop>) {
+    auto_loop.restart = None;
+}
+
+#[derive(Component)]
+pub struct LoopIndicator;
+
+fn sync_loop_indicator(
+    auto_loop: Res<AutoLoop>,
+    mut q_indicator: Query<&mut Visibility, With<LoopIndicator>>,
+) {
+    let visibility = if auto_loop.on {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut indicator in &mut q_indicator {
+        if *indicator != visibility {
+            *indicator = visibility;
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+pub struct TimerDigit;
+
+#[derive(Resource, Default)]
+pub struct GameTimer {
+    start: Option<Instant>,
+    elapsed: f32,
+    // the bot made a move this game, so the time isn't the player's own
+    bot_moved: bool,
+}
+
+impl GameTimer {
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Keeps this game's time off the best times. Cleared with the timer when
+    /// a new game starts.
+    pub fn mark_bot_move(&mut self) {
+        self.bot_moved = true;
+    }
+}
+
+// the timer stopped on the frame the game was won, before this runs
+fn record_best_time(
+    q_board: Query<&Board>,
+    timer: Res<GameTimer>,
+    mut stats: ResMut<Stats>,
+) {
+    if timer.bot_moved {
+        return;
+    }
+    if let Ok(board) = q_board.get_single() {
+        stats.record_time(board.difficulty(), timer.elapsed());
+    }
+}
+
+#[derive(Component)]
+pub struct BestTimeText;
+
+fn sync_best_time(
+    stats: Res<Stats>,
+    q_board: Query<&Board>,
+    mut q_text: Query<&mut Text, With<BestTimeText>>,
+) {
+    if let (Ok(board), Ok(mut text)) =
+        (q_board.get_single(), q_text.get_single_mut())
+    {
+        let best = match stats.best_time(board.difficulty()) {
+            Some(time) => format!("best {time:.1}s"),
+            None => "best --".to_string(),
+        };
+        // only touch the text when it changes, or it's laid out every frame
+        if text.sections[0].value != best {
+            text.sections[0].value = best;
+        }
+    }
+}
+
+fn sync_timer(
+    q_board: Query<&Board>,
+    app_state: Res<State<GameState>>,
+    paused: Res<Paused>,
+    mut timer: ResMut<GameTimer>,
+    mut q_digits: Query<(&mut TextureAtlasSprite, &TimerDigit)>,
+) {
+    if let Ok(board) = q_board.get_single() {
+        // clock starts on the first uncover and freezes once the game is over
+        if !board.first_uncovered() {
+            *timer = GameTimer::default();
+        } else if paused.0 {
+            // push the start back so the paused time isn't counted
+            let elapsed = Duration::from_secs_f32(timer.elapsed);
+            timer.start = Some(Instant::now() - elapsed);
+        } else if let Some(start) = timer.start {
+            if matches!(app_state.get(), GameState::Playing) {
+                timer.elapsed = start.elapsed().as_secs_f32();
+            }
+        } else {
+            timer.start = Some(Instant::now());
+        }
+        format!("{:03}", (timer.elapsed as usize).min(999))
+            .chars()
+            .map(BombCounterDigit::sheet_index)
+            .zip(q_digits.iter_mut())
+            .for_each(|(index, (mut sprite, _))| {
+                sprite.index = index;
+            });
+    }
+}
+
+/// How far the camera is zoomed in with the mouse wheel and panned by dragging
+/// with the middle button. Goes back to the whole window on a new game.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CameraView {
+    // world units per pixel, so below 1 is zoomed in
+    scale: f32,
+    pan: Vec2,
+    // pixels the mouse moved since the middle button went down
+    drag_distance: f32,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        CameraView {
+            scale: 1.0,
+            pan: Vec2::ZERO,
+            drag_distance: 0.0,
+        }
+    }
+}
+
+impl CameraView {
+    pub const MIN_SCALE: f32 = 0.2;
+    pub const MAX_SCALE: f32 = 1.0;
+    /// Pixels the middle button has to move before its release isn't a chord,
+    /// so a shaky click still chords
+    pub const DRAG_THRESHOLD: f32 = 4.0;
+
+    pub fn dragged(&self) -> bool {
+        self.drag_distance > Self::DRAG_THRESHOLD
+    }
+
+    /// Where the cursor would be in the window if the camera hadn't moved,
+    /// which is what the layout's hit tests work from
+    pub fn cursor_position(&self, window: &Window) -> Option<Vec2> {
+        let position = window.cursor_position()?;
+        let centre = Vec2::new(window.width(), window.height()) / 2.0;
+        Some(centre + (position - centre) * self.scale + self.pan * FLIP_Y)
+    }
+
+    /// Scales the view by `factor`, keeping still whatever is at `offset`
+    /// from the window's centre (y up), then keeps the view inside the window
+    pub fn zoom(&mut self, factor: f32, offset: Vec2, window_size: Vec2) {
+        let scale =
+            (self.scale * factor).clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+        self.pan += offset * (self.scale - scale);
+        self.scale = scale;
+        self.clamp_pan(window_size);
+    }
+
+    fn clamp_pan(&mut self, window_size: Vec2) {
+        let slack = window_size / 2.0 * (1.0 - self.scale);
+        self.pan = self.pan.clamp(-slack, slack);
+    }
+}
+
+// window coordinates run down from the top, world coordinates up
+const FLIP_Y: Vec2 = Vec2::new(1.0, -1.0);
+
+#[allow(clippy::too_many_arguments)]
+fn zoom_and_pan(
+    mut view: ResMut<CameraView>,
+    mut wheel: EventReader<MouseWheel>,
+    mut motion: EventReader<MouseMotion>,
+    mouse: Res<Input<MouseButton>>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<&mut Transform, With<Camera2d>>,
+    q_board: Query<Ref<Board>>,
+    mut started: Local<bool>,
+) {
+    if let Ok(board) = q_board.get_single() {
+        if board.is_added() || (*started && !board.first_uncovered()) {
+            *view = CameraView::default();
+        }
+        *started = board.first_uncovered();
+    }
+    let window = q_windows.single();
+    let window_size = Vec2::new(window.width(), window.height());
+    for event in wheel.iter() {
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 20.0,
+        };
+        let offset = window.cursor_position().map_or(Vec2::ZERO, |position| {
+            (position - window_size / 2.0) * FLIP_Y
+        });
+        view.zoom(0.9_f32.powf(lines), offset, window_size);
+    }
+    if mouse.just_pressed(MouseButton::Middle) {
+        view.drag_distance = 0.0;
+    }
+    if mouse.pressed(MouseButton::Middle) {
+        let scale = view.scale;
+        for event in motion.iter() {
+            view.pan -= event.delta * FLIP_Y * scale;
+            view.drag_distance += event.delta.length();
+        }
+        view.clamp_pan(window_size);
+    } else {
+        motion.clear();
+    }
+    for mut transform in &mut q_camera {
+        transform.translation.x = view.pan.x;
+        transform.translation.y = view.pan.y;
+        transform.scale = Vec3::new(view.scale, view.scale, 1.0);
+    }
+}
+
+/// Tile highlighted for keyboard play, hidden until an arrow key is pressed
+#[derive(Resource, Default)]
+pub struct Cursor {
+    pos: Option<TilePos>,
+}
+
+impl Cursor {
+    pub fn pos(&self) -> Option<TilePos> {
+        self.pos
+    }
+
+    fn reset(&mut self) {
+        self.pos = None;
+    }
+
+    // appears in the centre of the board first, then moves clamped to edges
+    fn step(&mut self, board: &Board, (d_col, d_row): (isize, isize)) {
+        self.pos = Some(match self.pos {
+            None => TilePos {
+                col: board.width() / 2,
+                row: board.height() / 2,
+            },
+            Some(TilePos { col, row }) => TilePos {
+                col: col.saturating_add_signed(d_col).min(board.width() - 1),
+                row: row.saturating_add_signed(d_row).min(board.height() - 1),
+            },
+        });
+    }
+}
+
+/// What the solver makes of the board, worked out once each time it changes
+/// while the player is up and shared by the overlays
+#[derive(Resource, Default)]
+pub struct BoardOdds {
+    cache: SolverCache,
+    // both sorted
+    safe: Vec<TilePos>,
+    bombs: Vec<TilePos>,
+    probabilities: HashMap<TilePos, f64>,
+    // the fewest and most bombs in the smallest group of tiles the numbers
+    // narrow down around each boundary tile, and the group's size
+    groups: HashMap<TilePos, (u8, u8, usize)>,
+}
+
+impl BoardOdds {
+    /// The solver runs on the main thread, so the odds settle for a rough
+    /// estimate rather than hold up a frame for long
+    const MAX_TIME: Duration = Duration::from_millis(200);
+
+    fn certainly_safe(&self, pos: TilePos) -> bool {
+        self.safe.binary_search(&pos).is_ok()
+    }
+
+    fn certainly_bomb(&self, pos: TilePos) -> bool {
+        self.bombs.binary_search(&pos).is_ok()
+    }
+
+    fn tightest_groups(
+        (min_bombs, max_bombs): (
+            HashMap<Vec<TilePos>, u8>,
+            HashMap<Vec<TilePos>, u8>,
+        ),
+    ) -> HashMap<TilePos, (u8, u8, usize)> {
+        // smallest and narrowest first, then in tile order to break ties
+        let mut narrowed = min_bombs
+            .keys()
+            .chain(max_bombs.keys())
+            .unique()
+            .map(|tiles| {
+                let len = tiles.len();
+                let min = min_bombs.get(tiles).copied().unwrap_or(0);
+                let max = max_bombs.get(tiles).copied().unwrap_or(len as u8);
+                (len, max.saturating_sub(min), tiles, min, max)
+            })
+            .filter(|&(len, _, _, min, max)| min > 0 || (max as usize) < len)
+            .collect_vec();
+        narrowed.sort();
+        let mut groups = HashMap::new();
+        for (len, _, tiles, min, max) in narrowed {
+            for &pos in tiles {
+                groups.entry(pos).or_insert((min, max, len));
+            }
+        }
+        groups
+    }
+}
+
+fn update_board_odds(
+    q_board: Query<Ref<Board>>,
+    app_state: Res<State<GameState>>,
+    agent_state: Res<State<AgentState>>,
+    budget: Res<GuessBudget>,
+    mut odds: ResMut<BoardOdds>,
+    mut stale: Local<bool>,
+) {
+    let Ok(board) = q_board.get_single() else {
+        return;
+    };
+    *stale |= board.is_changed() || app_state.is_changed();
+    // the bot keeps changing the board, so wait until it stops
+    if !*stale || !matches!(**agent_state, AgentState::Resting) {
+        return;
+    }
+    *stale = false;
+    if !matches!(app_state.get(), GameState::Playing) {
+        *odds = BoardOdds::default();
+        return;
+    }
+    let odds = &mut *odds;
+    (odds.safe, odds.bombs) =
+        agent::deduction_frontier_cached(&board, &mut odds.cache);
+    // every group is cached by now, so this doesn't solve anything again
+    odds.groups = BoardOdds::tightest_groups(
+        agent::deductions::get_subset_bounds_cached(&board, &mut odds.cache),
+    );
+    let budget = GuessBudget {
+        max_time: Some(BoardOdds::MAX_TIME),
+        ..*budget
+    };
+    odds.probabilities = agent::tile_probabilities(&board, budget);
+}
+
+#[derive(Resource, Default)]
+pub struct ShowHeatmap {
+    enabled: bool,
+}
+
+impl ShowHeatmap {
+    fn tint(&self, pos: TilePos, odds: &BoardOdds) -> Color {
+        match odds.probabilities.get(&pos) {
+            Some(&bomb_prob) if self.enabled => {
+                let bomb_prob = bomb_prob as f32;
+                Color::rgb(bomb_prob, 1.0 - bomb_prob, 0.0)
+            }
+            _ => Color::WHITE,
+        }
+    }
+}
+
+fn toggle_heatmap(keys: Res<Input<KeyCode>>, mut heatmap: ResMut<ShowHeatmap>) {
+    if keys.just_pressed(KeyCode::H) {
+        heatmap.enabled = !heatmap.enabled;
+    }
+}
+
+/// Toggled with A, tints every covered tile the agent is certain of: green if
+/// it's safe and red if it's a bomb, leaving only the guesses to the player
+#[derive(Resource, Default)]
+pub struct AssistOverlay {
+    enabled: bool,
+}
+
+impl AssistOverlay {
+    fn tint(&self, pos: TilePos, odds: &BoardOdds) -> Option<Color> {
+        if !self.enabled {
+            None
+        } else if odds.certainly_safe(pos) {
+            Some(Color::rgb(0.3, 1.0, 0.3))
+        } else if odds.certainly_bomb(pos) {
+            Some(Color::rgb(1.0, 0.3, 0.3))
+        } else {
+            None
+        }
+    }
+}
+
+fn toggle_assist_overlay(
+    keys: Res<Input<KeyCode>>,
+    mut assist: ResMut<AssistOverlay>,
+) {
+    if keys.just_pressed(KeyCode::A) {
+        assist.enabled = !assist.enabled;
+        println!("Assist {}", if assist.enabled { "on" } else { "off" });
+    }
+}
+
+/// A tile picked out by the hint key, tinted until the timer runs out without
+/// being uncovered
+#[derive(Resource)]
+pub struct HintFlash {
+    pos: TilePos,
+    // certainly safe, rather than only the best guess
+    safe: bool,
+    timer: Timer,
+}
+
+impl HintFlash {
+    const SECONDS: f32 = 1.5;
+
+    /// ? is shift and slash, so slash on its own is left free
+    pub fn requested(keys: &Input<KeyCode>) -> bool {
+        keys.just_pressed(KeyCode::Slash)
+            && keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    }
+
+    fn new(pos: TilePos, safe: bool) -> Self {
+        HintFlash {
+            pos,
+            safe,
+            timer: Timer::from_seconds(Self::SECONDS, TimerMode::Once),
+        }
+    }
+
+    fn tint(&self) -> Color {
+        if self.safe {
+            Color::rgb(0.4, 1.0, 0.4)
+        } else {
+            Color::rgb(1.0, 0.8, 0.2)
+        }
+    }
+}
+
+/// Flashes a tile the agent knows is safe when ? is pressed, or its best
+/// guess if nothing can be deduced
+fn check_hint(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    hint: Option<ResMut<HintFlash>>,
+    time: Res<Time>,
+    app_state: Res<State<GameState>>,
+    agent_state: Res<State<AgentState>>,
+    odds: Res<BoardOdds>,
+) {
+    if let Some(mut hint) = hint {
+        if hint.timer.tick(time.delta()).finished() {
+            commands.remove_resource::<HintFlash>();
+        }
+    }
+    if !HintFlash::requested(&keys)
+        || !matches!(app_state.get(), GameState::Playing)
+        || !matches!(agent_state.get(), AgentState::Resting)
+    {
+        return;
+    }
+    if let Some(&pos) = odds.safe.first() {
+        commands.insert_resource(HintFlash::new(pos, true));
+        return;
+    }
+    // ties go to the first tile so the same board always gets the same hint
+    let best_guess = odds
+        .probabilities
+        .iter()
+        .min_by(|(pos_a, a), (pos_b, b)| a.total_cmp(b).then(pos_a.cmp(pos_b)))
+        .map(|(&pos, _)| pos);
+    if let Some(pos) = best_guess {
+        commands.insert_resource(HintFlash::new(pos, false));
+    }
+}
+
+/// Sent for each tile a move changes, so a flood fill sends one per tile
+/// opened. Lets plugins react to reveals without diffing the board themselves.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct TileChanged {
+    pub pos: TilePos,
+    pub from: TileState,
+    pub to: TileState,
+}
+
+/// Text by the cursor with the odds of the covered tile under it
+#[derive(Component)]
+pub struct HoverTip;
+
+#[allow(clippy::too_many_arguments)]
+fn update_hover_tip(
+    q_board: Query<Ref<Board>>,
+    mut q_tip: Query<
+        (&mut Text, &mut Transform, &mut Visibility),
+        With<HoverTip>,
+    >,
+    (q_windows, view): (Query<&Window, With<PrimaryWindow>>, Res<CameraView>),
+    ui_sizing: Res<UISizing>,
+    app_state: Res<State<GameState>>,
+    agent_state: Res<State<AgentState>>,
+    odds: Res<BoardOdds>,
+) {
+    let (Ok(board), Ok((mut text, mut transform, mut visibility))) =
+        (q_board.get_single(), q_tip.get_single_mut())
+    else {
+        return;
+    };
+    let window = q_windows.single();
+    let resting = matches!(**agent_state, AgentState::Resting)
+        && matches!(app_state.get(), GameState::Playing);
+    let hovered = view
+        .cursor_position(window)
+        .and_then(|position| {
+            let pos = ui_sizing.clicked_tile_pos(position)?;
+            Some((position, pos))
+        })
+        .filter(|&(_, pos)| resting && board.tile_state(pos).covered());
+    let Some((position, pos)) = hovered else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    text.sections[0].value = if odds.certainly_safe(pos) {
+        "certain safe".to_string()
+    } else if odds.certainly_bomb(pos) {
+        "certain mine".to_string()
+    } else if let Some(bomb_prob) = odds.probabilities.get(&pos) {
+        let mut tip = format!("{:.0}% mine", 100.0 * bomb_prob);
+        // what the numbers pin down around it, e.g. "1-2 mines in 3 tiles"
+        if let Some(&(min, max, len)) = odds.groups.get(&pos) {
+            let count = if min == max {
+                min.to_string()
+            } else {
+                format!("{min}-{max}")
+            };
+            let mines = if max == 1 { "mine" } else { "mines" };
+            tip += &format!("\n{count} {mines} in {len} tiles");
+        }
+        tip
+    } else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    // just above the cursor, in world coordinates
+    transform.translation.x = position.x - window.width() / 2.0;
+    transform.translation.y =
+        window.height() / 2.0 - position.y + ui_sizing.tile_size / 2.0;
+    *visibility = Visibility::Visible;
+}
+
+/// Under the faces, the odds the player's best next move is safe, including
+/// the first click
+#[derive(Component)]
+pub struct DangerMeter;
+
+fn update_danger_meter(
+    q_board: Query<&Board>,
+    mut q_text: Query<&mut Text, With<DangerMeter>>,
+    agent_state: Res<State<AgentState>>,
+    odds: Res<BoardOdds>,
+    mut stale: Local<bool>,
+) {
+    let (Ok(board), Ok(mut text)) =
+        (q_board.get_single(), q_text.get_single_mut())
+    else {
+        return;
+    };
+    *stale |= odds.is_changed();
+    // the bot keeps changing the board, so wait until it stops
+    if !matches!(**agent_state, AgentState::Resting) {
+        if !text.sections[0].value.is_empty() {
+            text.sections[0].value.clear();
+            *stale = true;
+        }
+        return;
+    }
+    if !*stale {
+        return;
+    }
+    *stale = false;
+    text.sections[0].value = if board.result() != ActionResult::Continue {
+        String::new()
+    } else if !board.first_uncovered() {
+        // only an unprotected first click can hit a bomb
+        let bomb_prob = match board.first_click_policy() {
+            FirstClickPolicy::Unsafe => board.difficulty().bomb_density(),
+            _ => 0.0,
+        };
+        format!("{:.0}% safe", 100.0 * (1.0 - bomb_prob))
+    } else if !odds.safe.is_empty() || !odds.bombs.is_empty() {
+        "100% safe".to_string()
+    } else {
+        let best_bomb_prob = odds
+            .probabilities
+            .values()
+            .copied()
+            .min_by(f64::total_cmp)
+            .unwrap_or(0.0);
+        format!("{:.0}% safe", 100.0 * (1.0 - best_bomb_prob))
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
 fn sync_board_with_tile_sprites(
     q_board: Query<&Board>,
     mut q_tile_sprites: Query<(&mut TextureAtlasSprite, &TilePos)>,
     app_state: ResMut<State<GameState>>,
     agent_state: Res<State<AgentState>>,
     mouse: Res<Input<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    (q_windows, view): (Query<&Window, With<PrimaryWindow>>, Res<CameraView>),
     ui_sizing: Res<UISizing>,
+    (heatmap, assist, odds): (
+        Res<ShowHeatmap>,
+        Res<AssistOverlay>,
+        Res<BoardOdds>,
+    ),
+    cursor: Res<Cursor>,
+    paused: Res<Paused>,
+    hint: Option<Res<HintFlash>>,
+    loss_animation: Option<Res<LossAnimation>>,
     mut q_face_buttons: Query<
         (&mut TextureAtlasSprite, &FaceButton),
         Without<TilePos>,
     >,
 ) {
     if let Ok(board) = q_board.get_single() {
-        // check if mouse is down over a tile
-        let mut pressed = None;
-        if mouse.pressed(MouseButton::Left) {
-            if let Some(position) = q_windows.single().cursor_position() {
-                pressed = ui_sizing.clicked_tile_pos(position);
-            }
-        };
+        // check if mouse is over a tile, and whether it's down
+        let hovered = view
+            .cursor_position(q_windows.single())
+            .and_then(|position| ui_sizing.clicked_tile_pos(position));
+        let pressed = hovered.filter(|_| mouse.pressed(MouseButton::Left));
         // update tile appearence
         for (mut sprite, &pos) in &mut q_tile_sprites {
-            let tile_state = board.tile_state(pos);
+            let mut tile_state = board.tile_state(pos);
+            if let Some(animation) = &loss_animation {
+                tile_state = animation.shown_state(pos, tile_state);
+            }
+            sprite.color = if cursor.pos() == Some(pos) {
+                Color::rgb(0.6, 0.6, 1.0)
+            } else if let Some(hint) = hint
+                .as_ref()
+                .filter(|hint| hint.pos == pos && tile_state.covered())
+            {
+                hint.tint()
+            } else if tile_state.covered()
+                && hovered == Some(pos)
+                && matches!(app_state.get(), GameState::Playing)
+            {
+                // colours above 1 brighten the sprite
+                assist
+                    .tint(pos, &odds)
+                    .unwrap_or_else(|| heatmap.tint(pos, &odds))
+                    * 1.15
+            } else if tile_state.covered() {
+                assist
+                    .tint(pos, &odds)
+                    .unwrap_or_else(|| heatmap.tint(pos, &odds))
+            } else {
+                Color::WHITE
+            };
             if let Some(pressed_pos) = pressed {
                 if matches!(app_state.get(), GameState::Playing)
-                    && matches!(tile_state, TileState::Covered)
+                    && !paused.0
+                    && tile_state.covered()
                     && matches!(**agent_state, AgentState::Resting)
                     && pos == pressed_pos
                 {
@@ -336,42 +1804,439 @@ fn sync_board_with_tile_sprites(
     }
 }
 
-pub fn simulate_n_games(n: usize, difficulty: Difficulty, seed: u64) {
-    println!("Simulating {n} games on {difficulty}:\n");
+/// Result of one simulated game, as written to the CSV output
+struct GameSummary {
+    index: usize,
+    seed: u64,
+    result: ActionResult,
+    record: Record,
+    duration: f32,
+    timings: SolverTimings,
+    bbbv: usize,
+    /// Odds the guess that lost the game was safe
+    fatal_odds: Option<f64>,
+}
+
+// seeds follow the game index so runs are reproducible, hashed so that
+// neighbouring games don't share a first-click reroll
+fn derived_seed(seed: u64, index: usize) -> u64 {
+    let mut rng: StdRng =
+        SeedableRng::seed_from_u64(seed.wrapping_add(index as u64));
+    rng.gen()
+}
+
+/// How game `i` of a simulation is dealt from the run's seed, counting games
+/// from 1
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SeedScheme {
+    /// `seed + i` hashed, so neighbouring games share nothing
+    #[default]
+    Hashed,
+    /// `seed + i - 1` as is, failing rather than moving the seed on when the
+    /// first click isn't safe
+    Strict,
+    /// `seed ^ i` as is, so any game can be dealt again from its number alone.
+    /// Seeds are moved on by adding 1, so a game can end up the same as
+    /// another.
+    Xor,
+}
+
+impl SeedScheme {
+    pub fn game_seed(self, seed: u64, index: usize) -> u64 {
+        match self {
+            SeedScheme::Hashed => derived_seed(seed, index),
+            // never rerolled, so game 1 gets `seed` itself
+            SeedScheme::Strict => seed.wrapping_add(index as u64 - 1),
+            SeedScheme::Xor => seed ^ index as u64,
+        }
+    }
+}
+
+/// How the bot plays each simulated game and how the games are dealt
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SimulationOptions {
+    pub first_click_policy: FirstClickPolicy,
+    pub first_guess: FirstGuess,
+    pub guess_style: GuessStyle,
+    pub budget: GuessBudget,
+    pub skill: BotSkill,
+    pub seed_scheme: SeedScheme,
+    /// Moves seeds on past layouts that would force a coin flip
+    pub avoid_5050: bool,
+    /// Has the bot remember bombs rather than flag them
+    pub no_flags: bool,
+}
+
+fn simulate_game(
+    index: usize,
+    difficulty: Difficulty,
+    seed: u64,
+    options: &SimulationOptions,
+) -> io::Result<GameSummary> {
+    let SimulationOptions {
+        first_click_policy,
+        first_guess,
+        guess_style,
+        budget,
+        skill,
+        seed_scheme,
+        avoid_5050,
+        no_flags,
+    } = *options;
+    let mut board = Board::new(difficulty, Some(seed))
+        .with_first_click_policy(first_click_policy);
+    if avoid_5050 {
+        let first = first_guess.pos(&board);
+        agent::avoid_5050(&mut board, first);
+    }
+    if seed_scheme == SeedScheme::Strict
+        && !board.seed_is_playable(first_guess.pos(&board))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("game {index} (seed {seed}) has no safe first click"),
+        ));
+    }
+    let game_start = Instant::now();
     let mut record = Record::new(difficulty);
-    let mut longest_game: f32 = 0.0;
-    let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
+    let mut cache = SolverCache::default();
+    let mut last_was_guess = false;
+    let count_guesses = |_, origin| {
+        last_was_guess = origin == ActionOrigin::Guessed;
+        if last_was_guess {
+            record.total_guesses += 1;
+        }
+    };
+    let solve = if no_flags {
+        agent::solve_without_flags
+    } else {
+        agent::solve_cached
+    };
+    let result = solve(
+        &mut board,
+        &mut cache,
+        first_guess,
+        guess_style,
+        budget,
+        skill,
+        count_guesses,
+    );
+    record.add_game(&result, &board);
+    let mut fatal_odds = None;
+    if result == ActionResult::Lose {
+        info!("Game {index} lost on:\n{board}");
+        // the odds the solver chose the guess by, as it saw the board
+        if let Some(&Some(safety_prob)) =
+            cache.guess_odds().last().filter(|_| last_was_guess)
+        {
+            info!(
+                "Game {index} lost on a guess with {:.1}% survival odds",
+                100.0 * safety_prob
+            );
+            fatal_odds = Some(safety_prob);
+        }
+    }
+    Ok(GameSummary {
+        index,
+        seed: board.seed(),
+        result,
+        record,
+        duration: game_start.elapsed().as_secs_f32(),
+        timings: cache.timings(),
+        bbbv: board.compute_3bv(),
+        fatal_odds,
+    })
+}
+
+/// A board dealt for a dataset by `generate_boards`
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratedBoard {
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub bombs: Vec<TilePos>,
+    /// Tiles the bot left behind, if it played the board
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_states: Option<Vec<TileState>>,
+}
+
+#[cfg(feature = "serde")]
+impl GeneratedBoard {
+    /// Deals the board, or plays it with `agent::solve` first if `solved`. The
+    /// bot's first click can move the seed on, so its bombs are the ones kept.
+    pub fn new(difficulty: Difficulty, seed: u64, solved: bool) -> Self {
+        let mut board = Board::new(difficulty, Some(seed));
+        let tile_states = solved.then(|| {
+            agent::solve(&mut board);
+            board.tile_states().clone()
+        });
+        GeneratedBoard {
+            seed: board.seed(),
+            width: board.width(),
+            height: board.height(),
+            bombs: board.bomb_positions(),
+            tile_states,
+        }
+    }
+
+    /// A fresh board with the same bombs
+    pub fn board(&self) -> Result<Board, BoardError> {
+        Board::from_layout(self.width, self.height, &self.bombs)
+    }
+}
+
+/// Writes `count` boards to `path` as JSON lines, seeded from `seed` the same
+/// way as simulated games so the file can be made again
+#[cfg(feature = "serde")]
+pub fn generate_boards(
+    count: usize,
+    difficulty: Difficulty,
+    seed: u64,
+    solved: bool,
+    path: &Path,
+) -> io::Result<()> {
+    let boards = (1..=count)
+        .into_par_iter()
+        .map(|i| GeneratedBoard::new(difficulty, derived_seed(seed, i), solved))
+        .collect::<Vec<_>>();
+    let mut writer = io::BufWriter::new(File::create(path)?);
+    for board in boards {
+        serde_json::to_writer(&mut writer, &board)?;
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+/// Plays the agent on one hand-made board, printing each move it makes
+pub fn solve_layout(mut board: Board) {
+    println!(
+        "Solving {}x{} layout with {} bombs:\n",
+        board.width(),
+        board.height(),
+        board.num_bombs_total()
+    );
+    let mut cache = SolverCache::default();
+    let mut result = board.result();
+    while result == ActionResult::Continue {
+        let actions = agent::get_all_actions_cached(
+            &board,
+            &mut cache,
+            FirstGuess::default(),
+            GuessStyle::default(),
+            GuessBudget::default(),
+            BotSkill::default(),
+        );
+        // worth deducing again to say why each of these is certain
+        let reasons = match actions.first() {
+            Some((_, ActionOrigin::Deduced)) => {
+                agent::deductions::get_non_trivial_actions_explained(&board)
+            }
+            _ => vec![],
+        };
+        for (action, origin) in actions {
+            let TilePos { col, row } = action.pos;
+            println!("{:?} ({col}, {row}) {origin:?}", action.action_type);
+            if let Some((_, reason)) =
+                reasons.iter().find(|(x, _)| *x == action)
+            {
+                println!("    {reason}");
+            }
+            result = board.apply_action(action);
+            if result != ActionResult::Continue {
+                break;
+            }
+        }
+    }
+    match result {
+        ActionResult::Win => println!("\nSolved"),
+        _ => println!("\nHit a bomb"),
+    }
+}
+
+// draws over the tile sprites only, the board itself is untouched
+#[cfg(all(feature = "debug-reveal", debug_assertions))]
+fn reveal_bombs(
+    keys: Res<Input<KeyCode>>,
+    q_board: Query<&Board>,
+    mut q_tile_sprites: Query<(&mut TextureAtlasSprite, &TilePos)>,
+) {
+    if !keys.pressed(KeyCode::B) {
+        return;
+    }
+    if let Ok(board) = q_board.get_single() {
+        let bombs = board.bomb_positions();
+        for (mut sprite, pos) in &mut q_tile_sprites {
+            if bombs.contains(pos) && board.tile_state(*pos).covered() {
+                sprite.index = TileState::UncoveredBomb.sheet_index();
+                sprite.color = Color::rgb(1.0, 0.5, 1.0);
+            }
+        }
+    }
+}
+
+/// Plays `n` games with the agent and prints how it did. Games are numbered
+/// from 1, and game `i` is dealt from a seed worked out from `seed` by the
+/// options' `SeedScheme`.
+///
+/// Except with `SeedScheme::Strict`, a seed whose first click isn't safe is
+/// moved on by the board, the same way every time, so a game's seed from the
+/// scheme deals it again with the same settings.
+pub fn simulate_n_games(
+    n: usize,
+    difficulty: Difficulty,
+    seed: u64,
+    output: Option<PathBuf>,
+    threads: Option<usize>,
+    confidence: Option<f64>,
+    options: SimulationOptions,
+) -> io::Result<()> {
+    let SimulationOptions {
+        first_click_policy,
+        first_guess,
+        guess_style,
+        ..
+    } = options;
+    println!(
+        "Simulating {n} games on {difficulty} ({first_click_policy:?}, \
+         opening {first_guess:?}, {:?} guesses):\n",
+        guess_style.strategy
+    );
+    // made up front so a bad path fails before any games are played
+    let csv = output.map(File::create).transpose()?;
+    // rayon picks the number of threads itself when given 0
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0))
+        .build()
+        .map_err(io::Error::other)?;
+    let completed = AtomicUsize::new(0);
     let start = Instant::now();
-    for i in 1..=n {
-        let mut board = Board::new(difficulty, Some(rng.gen::<u64>()));
-        let game_start = Instant::now();
-        'game: loop {
-            for action in agent::get_all_actions(&board) {
-                let result = board.apply_action(action);
-                match result {
-                    ActionResult::Win | ActionResult::Lose => {
-                        end_game(&mut record, &result, &board);
-                        break 'game;
+    let mut summaries = pool.install(|| {
+        (1..=n)
+            .into_par_iter()
+            .map(|i| {
+                let game_seed = options.seed_scheme.game_seed(seed, i);
+                let summary =
+                    simulate_game(i, difficulty, game_seed, &options)?;
+                if summary.seed != game_seed {
+                    let moved = format!(
+                        "Game {i} seed {game_seed} moved on to {} for a safe \
+                         first click",
+                        summary.seed
+                    );
+                    // the seed in the CSV no longer follows the scheme
+                    if options.seed_scheme == SeedScheme::Xor {
+                        warn!("{moved}");
+                    } else {
+                        info!("{moved}");
                     }
-                    _ => {}
                 }
-            }
+                let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                info!(
+                    "Game {i} finished in {:.2}s (seed: {}), simulation \
+                     {:.2}% complete",
+                    summary.duration,
+                    summary.seed,
+                    100.0 * (done as f64 / n as f64)
+                );
+                Ok(summary)
+            })
+            .collect::<io::Result<Vec<_>>>()
+    })?;
+    summaries.sort_by_key(|summary| summary.index);
+    if let Some(file) = csv {
+        let mut writer = io::BufWriter::new(file);
+        writeln!(
+            writer,
+            "game,seed,outcome,bombs_cleared,total_bombs,duration,3bv"
+        )?;
+        for summary in &summaries {
+            write_summary(&mut writer, summary)?;
         }
-        longest_game = longest_game.max(game_start.elapsed().as_secs_f32());
-        println!(
-            "Game {i} finished in {:.2}s (seed: {})",
-            game_start.elapsed().as_secs_f32(),
-            board.seed()
-        );
+        writer.flush()?;
+    }
+    let elapsed = start.elapsed().as_secs_f32();
+    let mut record = Record::new(difficulty);
+    let mut longest_game: f32 = 0.0;
+    let mut timings = SolverTimings::default();
+    for summary in &summaries {
+        record.merge(&summary.record);
+        longest_game = longest_game.max(summary.duration);
+        timings.merge(&summary.timings);
+    }
+    println!(
+        "{}ms per game, {:.2}s in total, longest game took {:.2}s",
+        (1000.0 * elapsed / n as f32) as usize,
+        elapsed,
+        longest_game,
+    );
+    println!("{:.2} games/sec", n as f32 / elapsed);
+    println!("Solver time: {timings}");
+    println!("Record: {}", record);
+    let fatal_odds = summaries
+        .iter()
+        .filter_map(|summary| summary.fatal_odds)
+        .collect::<Vec<_>>();
+    if !fatal_odds.is_empty() {
         println!(
-            "{}ms per game, {:.2}s in total, longest game took {:.2}s",
-            (1000.0 * start.elapsed().as_secs_f32() / i as f32) as usize,
-            start.elapsed().as_secs_f32(),
-            longest_game,
+            "Fatal guesses by survival odds: {}",
+            odds_histogram(&fatal_odds)
         );
+    }
+    if let Some(confidence) = confidence {
+        let (low, high) = record.win_rate_interval(z_score(confidence));
         println!(
-            "Simulation {:.2}% complete\n",
-            100.0 * (i as f64 / n as f64)
+            "Win rate {:.2}%, {:.0}% confidence interval {:.2}% to {:.2}%",
+            100.0 * record.win_rate(),
+            100.0 * confidence,
+            100.0 * low,
+            100.0 * high,
         );
     }
+    Ok(())
+}
+
+/// Counts in bins 10% wide, leaving out empty ones, e.g. "50-60%: 4, 60-70%:
+/// 1". Mostly near 50% means the losses were coin flips, lower down means
+/// riskier bets.
+fn odds_histogram(odds: &[f64]) -> String {
+    let mut bins = [0; 10];
+    for &odds in odds {
+        bins[((odds * 10.0) as usize).min(9)] += 1;
+    }
+    bins.iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(i, count)| format!("{}-{}%: {count}", 10 * i, 10 * (i + 1)))
+        .join(", ")
+}
+
+// inverse of the standard normal CDF for a two-sided interval, using the
+// rational approximation from Abramowitz and Stegun 26.2.23 (error < 5e-4)
+fn z_score(confidence: f64) -> f64 {
+    let tail = (1.0 - confidence) / 2.0;
+    let t = (-2.0 * tail.ln()).sqrt();
+    t - (2.515517 + 0.802853 * t + 0.010328 * t * t)
+        / (1.0 + 1.432788 * t + 0.189269 * t * t + 0.001308 * t * t * t)
+}
+
+fn write_summary(
+    writer: &mut impl Write,
+    summary: &GameSummary,
+) -> io::Result<()> {
+    let outcome = match summary.result {
+        ActionResult::Win => "win",
+        ActionResult::Lose => "loss",
+        ActionResult::Continue => "dnf",
+    };
+    writeln!(
+        writer,
+        "{},{},{outcome},{},{},{:.3},{}",
+        summary.index,
+        summary.seed,
+        summary.record.total_bombs_cleared,
+        summary.record.total_bombs,
+        summary.duration,
+        summary.bbbv,
+    )
 }
@@ -1,9 +1,30 @@
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::DefaultPlugins;
-use clap::Parser;
-use minesweeper::setup::UISizing;
-use minesweeper::{simulate_n_games, Difficulty, GamePlugin};
+use clap::builder::RangedU64ValueParser;
+use clap::{error::ErrorKind, ArgAction, CommandFactory, Parser};
+use minesweeper::agent::{
+    BotSkill, FirstGuess, GuessBudget, GuessStrategy, GuessStyle,
+};
+use minesweeper::board::{
+    Adjacency, Board, BoardError, FirstClickPolicy, Topology, WinCondition,
+};
+use minesweeper::setup::{Theme, UISizing};
+#[cfg(not(target_family = "wasm"))]
+use minesweeper::tui;
+use minesweeper::{
+    simulate_n_games, solve_layout, BoardAdjacency, BoardTopology,
+    BoardWinCondition, Difficulty, FirstClick, GamePlugin, LossReveal,
+    SafeChord, SeedScheme, SimulationOptions, StrictFlags,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(feature = "serde")]
+use {
+    clap::Subcommand, minesweeper::generate_boards,
+    minesweeper::replay::Replay, minesweeper::Playback,
+    minesweeper::ReplayPath,
+};
 
 /// Minesweeper game: only need to pass arguments to run simulations
 #[derive(Parser, Debug)]
@@ -20,18 +41,341 @@ struct Args {
     /// Seed for simulated games
     #[arg(short, long, default_value_t)]
     seed: u64,
+
+    /// CSV file to write the result of each simulated game to
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Number of threads to simulate games on, defaults to one per core
+    #[arg(short, long)]
+    threads: Option<usize>,
+
+    /// What the first click of each game is guaranteed to uncover
+    #[arg(short, long, value_enum, default_value_t)]
+    first_click: FirstClickPolicy,
+
+    /// Where the bot opens: near-edge, center, corner or <col>,<row>
+    #[arg(short = 'g', long, default_value = "near-edge")]
+    first_guess: FirstGuess,
+
+    /// How the bot works out the odds when it has to guess
+    #[arg(long, value_enum, default_value_t)]
+    guess_strategy: GuessStrategy,
+
+    /// Largest group of boundary tiles the bot enumerates before making a
+    /// rough guess instead, at most 128
+    #[arg(long, default_value_t = GuessBudget::MAX_TILES,
+          value_parser = RangedU64ValueParser::<usize>::new().range(1..=128))]
+    guess_tiles: usize,
+
+    /// Milliseconds the bot may spend on the odds of a guess before making a
+    /// rough one instead. Results then depend on the machine.
+    #[arg(long)]
+    guess_ms: Option<u64>,
+
+    /// How much less safe than the safest a guess may be if it opens more of
+    /// the board early on, or is more constrained late on
+    #[arg(long, default_value_t = 0.0)]
+    progress_tolerance: f64,
+
+    /// How well the bot plays, from 0 to 1. Below 1 it sometimes misses a
+    /// deduction or guesses a riskier tile.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_skill)]
+    bot_skill: f64,
+
+    /// Confidence level of the win rate interval printed after simulating,
+    /// e.g. 0.95
+    #[arg(short, long, value_parser = parse_confidence)]
+    confidence: Option<f64>,
+
+    /// How each simulated game's seed is worked out from `seed`. Strict fails
+    /// rather than move a seed on when its first click isn't safe, and xor
+    /// warns when it does.
+    #[arg(long, value_enum, default_value_t)]
+    seed_scheme: SeedScheme,
+
+    /// Move each simulated game's seed on past layouts that would force the
+    /// bot into a coin flip. Not with the strict seed scheme.
+    #[arg(long)]
+    avoid_5050: bool,
+
+    /// Have the bot remember the bombs it finds in simulated games rather
+    /// than flag them. Bombs cleared then only counts won games.
+    #[arg(long)]
+    no_flags: bool,
+
+    /// Text file of `.` and `*` tiles for the bot to solve, one row per line.
+    /// The bot opens at column 2, halfway down, so keep that tile safe.
+    #[arg(short, long)]
+    layout: Option<PathBuf>,
+
+    /// Play in the terminal instead of a window
+    #[cfg(not(target_family = "wasm"))]
+    #[arg(long)]
+    tui: bool,
+
+    /// Game saved with S to play back in the window
+    #[cfg(feature = "serde")]
+    #[arg(short, long)]
+    replay: Option<PathBuf>,
+
+    /// Milliseconds between moves when playing back a replay
+    #[cfg(feature = "serde")]
+    #[arg(long, default_value_t = 500, requires = "replay")]
+    delay: u64,
+
+    /// File the S key saves the current game to, by default in the
+    /// platform's data directory
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    save_replay: Option<PathBuf>,
+
+    /// Lose as soon as a flag is placed on a safe tile
+    #[arg(long)]
+    strict_flags: bool,
+
+    /// Whether the edges of the board wrap around
+    #[arg(long, value_enum, default_value_t)]
+    topology: Topology,
+
+    /// Which tiles count as neighbours of a number
+    #[arg(long, value_enum, default_value_t)]
+    adjacency: Adjacency,
+
+    /// Whether every bomb has to be flagged to win
+    #[arg(long, value_enum, default_value_t)]
+    win_condition: WinCondition,
+
+    /// Only chord when the bot agrees the flags around the number are bombs
+    #[arg(long)]
+    safe_chord: bool,
+
+    /// Milliseconds the bombs take to appear one by one after a loss, 0 to
+    /// show them all at once
+    #[arg(long, default_value_t = LossReveal::DEFAULT_MILLIS)]
+    loss_reveal_ms: u64,
+
+    /// Height of the game window in pixels, the board is scaled to fit
+    #[arg(long, default_value_t = UISizing::DEFAULT_WINDOW_HEIGHT as u32,
+          value_parser = clap::value_parser!(u32)
+              .range(UISizing::MIN_WINDOW_HEIGHT as i64..))]
+    window_height: u32,
+
+    /// Folder under assets to load the sprites, padding and sounds from,
+    /// laid out like assets itself
+    #[arg(long)]
+    theme: Option<PathBuf>,
+
+    /// Log each simulated game, or the solver's decisions too if repeated
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Width of simulated games, overrides difficulty
+    #[arg(long, requires_all = ["height", "bombs"])]
+    width: Option<usize>,
+
+    /// Height of simulated games, overrides difficulty
+    #[arg(long, requires_all = ["width", "bombs"])]
+    height: Option<usize>,
+
+    /// Number of bombs in simulated games, overrides difficulty
+    #[arg(long, requires_all = ["width", "height"])]
+    bombs: Option<usize>,
+
+    #[cfg(feature = "serde")]
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write boards to a file as JSON lines, e.g. as a dataset
+    Generate {
+        /// Number of boards to write
+        #[arg(short, long)]
+        count: usize,
+
+        /// Difficulty of the boards
+        #[arg(short, long, value_enum, default_value_t)]
+        difficulty: Difficulty,
+
+        /// Seed the boards are derived from
+        #[arg(short, long, default_value_t)]
+        seed: u64,
+
+        /// JSON lines file to write the boards to
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Have the bot play each board and include the tiles it leaves
+        #[arg(long)]
+        solved: bool,
+    },
+}
+
+impl Args {
+    fn guess_budget(&self) -> GuessBudget {
+        let default = GuessBudget::default();
+        GuessBudget {
+            max_tiles: self.guess_tiles,
+            max_time: self
+                .guess_ms
+                .map(Duration::from_millis)
+                .or(default.max_time),
+            parallel: default.parallel,
+        }
+    }
+
+    fn guess_style(&self) -> GuessStyle {
+        GuessStyle {
+            strategy: self.guess_strategy,
+            progress_tolerance: self.progress_tolerance,
+        }
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        match (self.width, self.height, self.bombs) {
+            (Some(width), Some(height), Some(bombs)) => Difficulty::Custom {
+                width,
+                height,
+                bombs,
+            },
+            _ => self.difficulty,
+        }
+    }
+}
+
+fn parse_confidence(arg: &str) -> Result<f64, String> {
+    let confidence: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+    if confidence > 0.0 && confidence < 1.0 {
+        Ok(confidence)
+    } else {
+        Err("must be strictly between 0 and 1".to_string())
+    }
+}
+
+fn parse_skill(arg: &str) -> Result<f64, String> {
+    let skill: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+    if (0.0..=1.0).contains(&skill) {
+        Ok(skill)
+    } else {
+        Err("must be between 0 and 1".to_string())
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    #[cfg(feature = "serde")]
+    if let Some(Command::Generate {
+        count,
+        difficulty,
+        seed,
+        out,
+        solved,
+    }) = &args.command
+    {
+        if let Err(error) =
+            generate_boards(*count, *difficulty, *seed, *solved, out)
+        {
+            Args::command().error(ErrorKind::Io, error).exit();
+        }
+        return;
+    }
+    let difficulty = args.difficulty();
+    let budget = args.guess_budget();
+    let style = args.guess_style();
+    let (width, height) = difficulty.grid_size();
+    let num_tiles = width * height;
+    if difficulty.num_bombs() >= num_tiles {
+        let error = BoardError::TooManyBombs {
+            num_bombs: difficulty.num_bombs(),
+            num_tiles,
+        };
+        Args::command()
+            .error(ErrorKind::ValueValidation, error)
+            .exit();
+    }
+    if let Some(path) = &args.layout {
+        let board = std::fs::read_to_string(path)
+            .map_err(|error| Args::command().error(ErrorKind::Io, error))
+            .and_then(|layout| {
+                Board::parse_layout(&layout).map_err(|error| {
+                    Args::command().error(ErrorKind::InvalidValue, error)
+                })
+            })
+            .unwrap_or_else(|error| error.exit());
+        solve_layout(board);
+        return;
+    }
+    #[cfg(not(target_family = "wasm"))]
+    if args.tui {
+        if let Err(error) = tui::run(difficulty, args.first_click) {
+            Args::command().error(ErrorKind::Io, error).exit();
+        }
+        return;
+    }
     if args.num_games > 0 {
-        simulate_n_games(args.num_games, args.difficulty, args.seed);
+        // no window, so the logger is installed without an app
+        let level = match args.verbose {
+            0 => bevy::log::Level::WARN,
+            1 => bevy::log::Level::INFO,
+            _ => bevy::log::Level::DEBUG,
+        };
+        tracing_subscriber::fmt().with_max_level(level).init();
+        if args.avoid_5050 && args.seed_scheme == SeedScheme::Strict {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--avoid-5050 moves seeds on, which the strict seed \
+                     scheme doesn't allow",
+                )
+                .exit();
+        }
+        let options = SimulationOptions {
+            first_click_policy: args.first_click,
+            first_guess: args.first_guess,
+            guess_style: style,
+            budget,
+            skill: BotSkill(args.bot_skill),
+            seed_scheme: args.seed_scheme,
+            avoid_5050: args.avoid_5050,
+            no_flags: args.no_flags,
+        };
+        let output = args.output.clone();
+        if let Err(error) = simulate_n_games(
+            args.num_games,
+            difficulty,
+            args.seed,
+            output,
+            args.threads,
+            args.confidence,
+            options,
+        ) {
+            eprintln!("Simulation failed: {error}");
+        }
         return;
     }
-    let ui_sizing = UISizing::new(Difficulty::default().grid_size());
+    #[cfg(feature = "serde")]
+    let playback = args.replay.as_ref().map(|path| {
+        let replay = Replay::load(path).unwrap_or_else(|error| {
+            Args::command().error(ErrorKind::Io, error).exit()
+        });
+        Playback::new(replay, Duration::from_millis(args.delay))
+    });
+    let ui_sizing = UISizing::new_with_height(
+        Difficulty::default().grid_size(),
+        args.window_height as f32,
+    );
     let window_size = ui_sizing.window_size;
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.75, 0.75, 0.75)))
+    let theme = args.theme.map_or_else(Theme::default, |dir| {
+        Theme::new(dir).unwrap_or_else(|error| {
+            eprintln!("{error}, using the default theme");
+            Theme::default()
+        })
+    });
+    let mut app = App::new();
+    app.insert_resource(ClearColor(Color::rgb(0.75, 0.75, 0.75)))
         .insert_resource(ui_sizing)
         .add_plugins(
             DefaultPlugins
@@ -43,7 +387,7 @@ fn main() {
                         canvas: Some("#bevy".to_owned()),
                         // Tells wasm not to override default event handling
                         prevent_default_event_handling: false,
-                        resizable: false,
+                        resizable: true,
                         ..default()
                     }),
                     ..default()
@@ -54,5 +398,26 @@ fn main() {
                 }),
         )
         .add_plugins((GamePlugin, bevy_framepace::FramepacePlugin))
-        .run();
+        .insert_resource(budget)
+        .insert_resource(style)
+        .insert_resource(BotSkill(args.bot_skill))
+        .insert_resource(theme)
+        .insert_resource(StrictFlags(args.strict_flags))
+        .insert_resource(FirstClick(args.first_click))
+        .insert_resource(BoardTopology(args.topology))
+        .insert_resource(BoardAdjacency(args.adjacency))
+        .insert_resource(BoardWinCondition(args.win_condition))
+        .insert_resource(SafeChord(args.safe_chord))
+        .insert_resource(LossReveal(Duration::from_millis(
+            args.loss_reveal_ms,
+        )));
+    #[cfg(feature = "serde")]
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+    #[cfg(feature = "serde")]
+    if let Some(path) = args.save_replay {
+        app.insert_resource(ReplayPath(Some(path)));
+    }
+    app.run();
 }
@@ -0,0 +1,169 @@
+use crate::board::{
+    Action, ActionResult, Adjacency, Board, BoardSnapshot, FirstClickPolicy,
+    Topology, WinCondition,
+};
+use crate::Difficulty;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+#[cfg(feature = "serde")]
+use {
+    serde::{Deserialize, Serialize},
+    std::fs::File,
+    std::io::{self, BufReader, BufWriter},
+    std::path::Path,
+};
+
+/// Every move that changed the board in the current game, in order, along
+/// with what's needed to deal the same bombs again under the same rules. The
+/// last few moves can be undone, and redone until another move is made.
+#[derive(Resource, Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Replay {
+    difficulty: Difficulty,
+    seed: u64,
+    // older replays were all played with the default rules
+    #[cfg_attr(feature = "serde", serde(default))]
+    first_click_policy: FirstClickPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    topology: Topology,
+    #[cfg_attr(feature = "serde", serde(default))]
+    adjacency: Adjacency,
+    #[cfg_attr(feature = "serde", serde(default))]
+    win_condition: WinCondition,
+    #[cfg_attr(feature = "serde", serde(default))]
+    strict_flags: bool,
+    actions: Vec<Action>,
+    // the board before each of the last moves, oldest first
+    #[cfg_attr(feature = "serde", serde(skip))]
+    history: VecDeque<BoardSnapshot>,
+    // undone moves with the board they left, the latest last
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undone: Vec<(Action, BoardSnapshot)>,
+}
+
+impl Replay {
+    /// Moves that can be undone in a row, older ones are forgotten
+    pub const MAX_UNDO: usize = 100;
+
+    /// Applies the move to the board, recording it if it changed anything
+    pub fn apply(&mut self, board: &mut Board, action: Action) -> ActionResult {
+        // the first uncover resamples the bombs starting from the current seed
+        if !board.first_uncovered() {
+            self.difficulty = board.difficulty();
+            self.seed = board.seed();
+            self.first_click_policy = board.first_click_policy();
+            self.topology = board.topology();
+            self.adjacency = board.adjacency();
+            self.win_condition = board.win_condition();
+            self.strict_flags = board.strict_flags();
+        }
+        let before = board.snapshot();
+        let result = board.apply_action(action);
+        // only moves that did something are recorded or undone
+        if board.changed_since(&before) {
+            self.actions.push(action);
+            self.remember(before);
+            self.undone.clear();
+        }
+        result
+    }
+
+    fn remember(&mut self, snapshot: BoardSnapshot) {
+        if self.history.len() == Self::MAX_UNDO {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Takes the last move back off the board, returning false if there are
+    /// no moves left to undo
+    pub fn undo(&mut self, board: &mut Board) -> bool {
+        let Some(snapshot) = self.history.pop_back() else {
+            return false;
+        };
+        // every remembered board has its move still recorded
+        let action = self.actions.pop().unwrap();
+        self.undone.push((action, board.snapshot()));
+        board.restore(snapshot);
+        true
+    }
+
+    /// Makes the last undone move again, returning false if there isn't one
+    pub fn redo(&mut self, board: &mut Board) -> bool {
+        let Some((action, snapshot)) = self.undone.pop() else {
+            return false;
+        };
+        self.actions.push(action);
+        self.remember(board.snapshot());
+        board.restore(snapshot);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+        self.history.clear();
+        self.undone.clear();
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// The board as it was before the first move
+    pub fn board(&self) -> Board {
+        Board::new(self.difficulty, Some(self.seed))
+            .with_first_click_policy(self.first_click_policy)
+            .with_topology(self.topology)
+            .with_adjacency(self.adjacency)
+            .with_win_condition(self.win_condition)
+            .with_strict_flags(self.strict_flags)
+    }
+
+    /// Applies every move to a fresh board
+    pub fn play(&self) -> Board {
+        let mut board = self.board();
+        for &action in &self.actions {
+            board.apply_action(action);
+        }
+        board
+    }
+}
+
+// the same game whatever could be undone, as that isn't saved
+impl PartialEq for Replay {
+    fn eq(&self, other: &Self) -> bool {
+        self.difficulty == other.difficulty
+            && self.seed == other.seed
+            && self.first_click_policy == other.first_click_policy
+            && self.topology == other.topology
+            && self.adjacency == other.adjacency
+            && self.win_condition == other.win_condition
+            && self.strict_flags == other.strict_flags
+            && self.actions == other.actions
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Replay {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
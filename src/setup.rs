@@ -1,21 +1,177 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::asset::Asset;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::window::{PrimaryWindow, WindowResized};
 use bevy_framepace::{FramepaceSettings, Limiter};
+use std::error::Error;
 use std::f32::consts::PI;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    image::imageops::{self, FilterType},
+    image::{ImageResult, RgbaImage},
+    std::collections::HashMap,
+    std::path::Path,
+};
 
-const WINDOW_HEIGHT: f32 = 700.0;
 const TILE_SPRITE_SIZE: f32 = 16.0;
 const EDGE_PADDING_SPRITE_SIZE: f32 = 12.0;
 const TOP_PADDING_SPRITE_SIZE: f32 = 60.0;
 const BOT_SPRITE_SIZE: f32 = 384.0;
 const FACE_SPRITE_SIZE: f32 = 24.0;
 const DIGIT_SPRITE_SIZE: (f32, f32) = (13.0, 23.0);
+/// 4 by 4 tiles, in the order of `TileState::sheet_index`
+const TILES_SPRITESHEET: &str = "spritesheets/minesweeper_tiles.png";
 
 use crate::{
     board::{Board, TileState},
-    AgentState, BombCounterDigit, BotButton, Difficulty, FaceButton, Record,
-    TilePos,
+    AgentState, BestTimeText, BoardAdjacency, BoardTopology, BoardWinCondition,
+    BombCounterDigit, BotButton, DangerMeter, Difficulty, FaceButton,
+    FirstClick, HoverTip, LoopIndicator, SafeTilesDigit, Sounds, StrictFlags,
+    TilePos, TimerDigit,
 };
 
+// every sheet at the size `TextureAtlas::from_grid` cuts it up at
+const SPRITESHEETS: [(&str, (u32, u32)); 6] = [
+    (TILES_SPRITESHEET, (64, 64)),
+    ("spritesheets/bot_tiles.png", (768, 384)),
+    ("spritesheets/bot_one_tiles.png", (768, 384)),
+    ("spritesheets/bot_flag_tiles.png", (768, 384)),
+    ("spritesheets/faces.png", (144, 72)),
+    ("spritesheets/numbers.png", (167, 23)),
+];
+const OTHER_ASSETS: [&str; 10] = [
+    "padding/padding.png",
+    "padding/top_left_corner.png",
+    "padding/top_right_corner.png",
+    "padding/bottom_left_corner.png",
+    "padding/bottom_right_corner.png",
+    "padding/middle_left_connecter.png",
+    "padding/middle_right_connecter.png",
+    "sounds/win.wav",
+    "sounds/lose.wav",
+    "sounds/click.wav",
+];
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Unreadable(PathBuf),
+    WrongSize {
+        path: PathBuf,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+}
+
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Unreadable(path) => {
+                write!(f, "couldn't read {}", path.display())
+            }
+            ThemeError::WrongSize {
+                path,
+                expected: (expected_width, expected_height),
+                found: (width, height),
+            } => write!(
+                f,
+                "{} is {width}x{height} pixels rather than \
+                 {expected_width}x{expected_height}",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Error for ThemeError {}
+
+/// Folder under `assets` that the sprites, padding and sounds are loaded
+/// from, laid out like `assets` itself. The default is `assets`.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Theme {
+    dir: PathBuf,
+}
+
+impl Theme {
+    /// Checks the theme has every asset, with each spritesheet the same size
+    /// as the default one so the sprites are cut from it in the same places
+    pub fn new(dir: PathBuf) -> Result<Theme, ThemeError> {
+        let theme = Theme { dir };
+        // the browser fetches assets as it goes, so can't check ahead
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            for (sheet, expected) in SPRITESHEETS {
+                let path = theme.file(sheet);
+                let found = image::image_dimensions(&path)
+                    .map_err(|_| ThemeError::Unreadable(path.clone()))?;
+                if found != expected {
+                    return Err(ThemeError::WrongSize {
+                        path,
+                        expected,
+                        found,
+                    });
+                }
+            }
+            for asset in OTHER_ASSETS {
+                let path = theme.file(asset);
+                if !path.is_file() {
+                    return Err(ThemeError::Unreadable(path));
+                }
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Where `asset` is in this theme, relative to `assets`
+    pub fn path(&self, asset: &str) -> PathBuf {
+        self.dir.join(asset)
+    }
+
+    /// Where `asset` is on disk
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn file(&self, asset: &str) -> PathBuf {
+        bevy::asset::FileAssetIo::get_base_path()
+            .join("assets")
+            .join(self.path(asset))
+    }
+}
+
+/// The asset server, loading from the current theme
+#[derive(SystemParam)]
+pub struct ThemedAssets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    theme: Res<'w, Theme>,
+}
+
+impl ThemedAssets<'_> {
+    pub fn load<T: Asset>(&self, asset: &str) -> Handle<T> {
+        self.asset_server.load(self.theme.path(asset))
+    }
+}
+
+/// The rules set from the command line that each new board is played by
+#[derive(SystemParam)]
+pub struct BoardSettings<'w> {
+    strict_flags: Res<'w, StrictFlags>,
+    first_click: Res<'w, FirstClick>,
+    topology: Res<'w, BoardTopology>,
+    adjacency: Res<'w, BoardAdjacency>,
+    win_condition: Res<'w, BoardWinCondition>,
+}
+
+impl BoardSettings<'_> {
+    fn new_board(&self, difficulty: Difficulty) -> Board {
+        Board::new(difficulty, None)
+            .with_strict_flags(self.strict_flags.0)
+            .with_first_click_policy(self.first_click.0)
+            .with_topology(self.topology.0)
+            .with_adjacency(self.adjacency.0)
+            .with_win_condition(self.win_condition.0)
+    }
+}
+
 #[derive(Resource, Debug, Clone)]
 pub struct UISizing {
     pub window_size: (f32, f32),
@@ -28,11 +184,22 @@ pub struct UISizing {
 }
 
 impl UISizing {
-    pub fn new((width, height): (usize, usize)) -> Self {
+    pub const DEFAULT_WINDOW_HEIGHT: f32 = 700.0;
+    pub const MIN_WINDOW_HEIGHT: f32 = 200.0;
+
+    pub fn new(grid_size: (usize, usize)) -> Self {
+        UISizing::new_with_height(grid_size, UISizing::DEFAULT_WINDOW_HEIGHT)
+    }
+
+    /// Scales the board and padding so the window is `window_height` tall
+    pub fn new_with_height(
+        (width, height): (usize, usize),
+        window_height: f32,
+    ) -> Self {
         let unscaled_height = height as f32 * TILE_SPRITE_SIZE
             + EDGE_PADDING_SPRITE_SIZE
             + TOP_PADDING_SPRITE_SIZE;
-        let scale = WINDOW_HEIGHT / unscaled_height;
+        let scale = window_height / unscaled_height;
         let tile_size = TILE_SPRITE_SIZE * scale;
         let edge_padding = EDGE_PADDING_SPRITE_SIZE * scale;
         let top_padding = TOP_PADDING_SPRITE_SIZE * scale;
@@ -40,7 +207,7 @@ impl UISizing {
         let board_height = tile_size * height as f32;
         let window_width = board_width + 2.0 * edge_padding;
         UISizing {
-            window_size: (window_width, WINDOW_HEIGHT),
+            window_size: (window_width, window_height),
             board_size: (board_width, board_height),
             grid_size: (width, height),
             tile_size,
@@ -50,6 +217,13 @@ impl UISizing {
         }
     }
 
+    /// Lays the same grid out for a window resized to `window_height`, or
+    /// `None` below the minimum height, as when the window is minimised
+    pub fn resized(&self, window_height: f32) -> Option<Self> {
+        (window_height >= UISizing::MIN_WINDOW_HEIGHT)
+            .then(|| UISizing::new_with_height(self.grid_size, window_height))
+    }
+
     pub fn pos_on_board(&self, &TilePos { col, row }: &TilePos) -> Vec3 {
         let &UISizing {
             tile_size,
@@ -81,48 +255,106 @@ impl UISizing {
     }
 }
 
+/// Draws the board as it looks in game, with each tile cut from the theme's
+/// tile spritesheet and scaled to `ui_sizing`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_png(
+    board: &Board,
+    path: &Path,
+    theme: &Theme,
+    ui_sizing: &UISizing,
+) -> ImageResult<()> {
+    let sheet = image::open(theme.file(TILES_SPRITESHEET))?.to_rgba8();
+    let sprite_size = TILE_SPRITE_SIZE as u32;
+    let columns = sheet.width() / sprite_size;
+    let tile_pixels = (ui_sizing.tile_size.round() as u32).max(1);
+    let mut image = RgbaImage::new(
+        board.width() as u32 * tile_pixels,
+        board.height() as u32 * tile_pixels,
+    );
+    let mut sprites = HashMap::new();
+    for (TilePos { col, row }, state) in board.tiles() {
+        let sprite = sprites.entry(state.sheet_index()).or_insert_with(|| {
+            let index = state.sheet_index() as u32;
+            let sprite = imageops::crop_imm(
+                &sheet,
+                index % columns * sprite_size,
+                index / columns * sprite_size,
+                sprite_size,
+                sprite_size,
+            );
+            // nearest keeps the pixel art sharp
+            imageops::resize(
+                &*sprite,
+                tile_pixels,
+                tile_pixels,
+                FilterType::Nearest,
+            )
+        });
+        imageops::replace(
+            &mut image,
+            sprite,
+            (col as u32 * tile_pixels).into(),
+            (row as u32 * tile_pixels).into(),
+        );
+    }
+    image.save(path)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn setup(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    asset_server: ThemedAssets,
     texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut settings: ResMut<FramepaceSettings>,
     q_windows: Query<&mut Window, With<PrimaryWindow>>,
     ui_sizing: Res<UISizing>,
     difficulty: Res<State<Difficulty>>,
+    board_settings: BoardSettings,
 ) {
     settings.limiter = Limiter::from_framerate(50.0);
+    commands.insert_resource(Sounds {
+        win: asset_server.load("sounds/win.wav"),
+        lose: asset_server.load("sounds/lose.wav"),
+        click: asset_server.load("sounds/click.wav"),
+    });
     setup_game(
         &mut commands,
         asset_server,
         texture_atlases,
         q_windows,
         ui_sizing,
-        **difficulty,
+        board_settings.new_board(**difficulty),
     );
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn resize(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    asset_server: ThemedAssets,
     texture_atlases: ResMut<Assets<TextureAtlas>>,
     q_windows: Query<&mut Window, With<PrimaryWindow>>,
     mut ui_sizing: ResMut<UISizing>,
     game_objects: Query<Entity, Without<Window>>,
     next_difficulty: ResMut<NextState<Difficulty>>,
+    board_settings: BoardSettings,
 ) {
     let new_difficulty = match next_difficulty.0 {
         Some(new_difficulty) => new_difficulty,
         None => return,
     };
     println!("\nChanging difficulty level to {}\n", new_difficulty);
-    *ui_sizing = UISizing::new(new_difficulty.grid_size());
+    // keep whatever height the window was given
+    let window_height = ui_sizing.window_size.1;
+    *ui_sizing =
+        UISizing::new_with_height(new_difficulty.grid_size(), window_height);
     setup_game(
         &mut commands,
         asset_server,
         texture_atlases,
         q_windows,
         ui_sizing.into(),
-        new_difficulty,
+        board_settings.new_board(new_difficulty),
     );
     // despawn old
     for entity in &game_objects {
@@ -130,14 +362,65 @@ pub fn resize(
     }
 }
 
+/// Lays the game out again to fit the window's new height, carrying the board
+/// over as it is
+#[allow(clippy::too_many_arguments)]
+pub fn relayout(
+    mut commands: Commands,
+    asset_server: ThemedAssets,
+    texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut ui_sizing: ResMut<UISizing>,
+    game_objects: Query<Entity, Without<Window>>,
+    q_board: Query<&Board>,
+    next_difficulty: Res<NextState<Difficulty>>,
+    mut resized: EventReader<WindowResized>,
+) {
+    let Some(event) = resized.iter().last() else {
+        return;
+    };
+    // a new difficulty is laid out from scratch by `resize`
+    if next_difficulty.0.is_some() {
+        return;
+    }
+    let Some(new_sizing) = ui_sizing.resized(event.height) else {
+        return;
+    };
+    // laying out sets the window size, which comes back as another event.
+    // Only the height decides the layout, so a new width just snaps back
+    let (width, height) = ui_sizing.window_size;
+    if (event.height - height).abs() < 1.0 {
+        if (event.width - width).abs() >= 1.0 {
+            q_windows.single_mut().resolution.set(width, height);
+        }
+        return;
+    }
+    let Ok(board) = q_board.get_single() else {
+        return;
+    };
+    *ui_sizing = new_sizing;
+    setup_game(
+        &mut commands,
+        asset_server,
+        texture_atlases,
+        q_windows,
+        ui_sizing.into(),
+        board.clone(),
+    );
+    for entity in &game_objects {
+        commands.entity(entity).despawn();
+    }
+}
+
 fn setup_game(
     commands: &mut Commands,
-    asset_server: Res<AssetServer>,
+    asset_server: ThemedAssets,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut q_windows: Query<&mut Window, With<PrimaryWindow>>,
     ui_sizing: Res<UISizing>,
-    difficulty: Difficulty,
+    board: Board,
 ) {
+    let difficulty = board.difficulty();
     let (width, height) = ui_sizing.window_size;
     q_windows.single_mut().resolution.set(width, height);
     commands.spawn(Camera2dBundle::default());
@@ -145,10 +428,11 @@ fn setup_game(
         commands,
         &asset_server,
         &mut texture_atlases,
-        difficulty,
+        board,
         &ui_sizing,
     );
     spawn_padding(commands, &asset_server, &ui_sizing);
+    spawn_hover_tip(commands, &ui_sizing);
     // pretty cramped on easy, so scale down buttons and display
     let mut ui_sizing = (*ui_sizing).clone();
     if matches!(difficulty, Difficulty::Easy) {
@@ -161,14 +445,47 @@ fn setup_game(
         &mut texture_atlases,
         &ui_sizing,
     );
-    commands.spawn(Record::new(difficulty));
+    spawn_timer_display(
+        commands,
+        &asset_server,
+        &mut texture_atlases,
+        &ui_sizing,
+    );
+    spawn_safe_tiles_display(
+        commands,
+        &asset_server,
+        &mut texture_atlases,
+        &ui_sizing,
+    );
+    spawn_best_time_display(commands, &ui_sizing);
+    spawn_loop_indicator(commands, &ui_sizing);
+    spawn_danger_meter(commands, &ui_sizing);
+}
+
+fn spawn_hover_tip(commands: &mut Commands, ui_sizing: &UISizing) {
+    let style = TextStyle {
+        font_size: 10.0 * ui_sizing.scale,
+        color: Color::BLACK,
+        ..default()
+    };
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style),
+            text_anchor: Anchor::BottomCenter,
+            // above the tiles and padding
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        HoverTip,
+    ));
 }
 
 fn spawn_board(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    asset_server: &ThemedAssets,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    difficulty: Difficulty,
+    board: Board,
     ui_sizing: &UISizing,
 ) {
     let &UISizing {
@@ -177,8 +494,7 @@ fn spawn_board(
         scale,
         ..
     } = ui_sizing;
-    let texture_handle =
-        asset_server.load("spritesheets/minesweeper_tiles.png");
+    let texture_handle = asset_server.load(TILES_SPRITESHEET);
     let texture_atlas = TextureAtlas::from_grid(
         texture_handle,
         Vec2::splat(TILE_SPRITE_SIZE),
@@ -188,7 +504,6 @@ fn spawn_board(
         None,
     );
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    let board = Board::new(difficulty, None);
     let (width, height) = (board.width(), board.height());
     commands
         .spawn(board)
@@ -221,7 +536,36 @@ fn spawn_board(
 
 fn spawn_buttons(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    asset_server: &ThemedAssets,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    ui_sizing: &UISizing,
+) {
+    // stacked in a column between the faces and the timer
+    let bot_buttons = [
+        ("spritesheets/bot_tiles.png", AgentState::Thinking),
+        (
+            "spritesheets/bot_one_tiles.png",
+            AgentState::ThinkingOneMoveOnly,
+        ),
+        ("spritesheets/bot_flag_tiles.png", AgentState::FlaggingOnly),
+    ];
+    for (i, (path, bot_effect)) in bot_buttons.into_iter().enumerate() {
+        spawn_bot_button(
+            commands,
+            asset_server,
+            texture_atlases,
+            ui_sizing,
+            path,
+            1.0 - i as f32,
+            bot_effect,
+        );
+    }
+    spawn_face_buttons(commands, asset_server, texture_atlases, ui_sizing);
+}
+
+fn spawn_bot_button(
+    commands: &mut Commands,
+    asset_server: &ThemedAssets,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
     &UISizing {
         window_size,
@@ -230,8 +574,11 @@ fn spawn_buttons(
         scale,
         ..
     }: &UISizing,
+    path: &'static str,
+    vertical_offset: f32,
+    bot_effect: AgentState,
 ) {
-    let texture_handle = asset_server.load("spritesheets/bot_tiles.png");
+    let texture_handle = asset_server.load(path);
     let texture_atlas = TextureAtlas::from_grid(
         texture_handle,
         Vec2::splat(BOT_SPRITE_SIZE),
@@ -241,11 +588,12 @@ fn spawn_buttons(
         None,
     );
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    let size = 1.5 * TILE_SPRITE_SIZE;
+    let size = TILE_SPRITE_SIZE;
     let transform = Transform {
         translation: Vec3::new(
-            (window_size.0 - 2.0 * edge_padding) * 0.3,
-            (window_size.1 - top_padding) / 2.0,
+            (window_size.0 - 2.0 * edge_padding) * 0.25,
+            (window_size.1 - top_padding) / 2.0
+                + vertical_offset * 1.125 * size * scale,
             1.0,
         ),
         scale: Vec3::splat(size * scale / BOT_SPRITE_SIZE),
@@ -259,45 +607,7 @@ fn spawn_buttons(
             ..default()
         },
         BotButton {
-            bot_effect: AgentState::Thinking,
-            unpressed_index: 0,
-            pressed_index: 1,
-        },
-        crate::Button {
-            location: Rect::from_center_size(
-                transform.translation.truncate(),
-                Vec2::splat(size * scale),
-            ),
-        },
-    ));
-    let texture_handle = asset_server.load("spritesheets/bot_one_tiles.png");
-    let texture_atlas = TextureAtlas::from_grid(
-        texture_handle,
-        Vec2::splat(BOT_SPRITE_SIZE),
-        2,
-        1,
-        None,
-        None,
-    );
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    let transform = Transform {
-        translation: Vec3::new(
-            (window_size.0 - 2.0 * edge_padding) * 0.4,
-            (window_size.1 - top_padding) / 2.0,
-            1.0,
-        ),
-        scale: Vec3::splat(size * scale / BOT_SPRITE_SIZE),
-        ..default()
-    };
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle,
-            sprite: TextureAtlasSprite::new(0),
-            transform,
-            ..default()
-        },
-        BotButton {
-            bot_effect: AgentState::ThinkingOneMoveOnly,
+            bot_effect,
             unpressed_index: 0,
             pressed_index: 1,
         },
@@ -308,11 +618,24 @@ fn spawn_buttons(
             ),
         },
     ));
+}
+
+fn spawn_face_buttons(
+    commands: &mut Commands,
+    asset_server: &ThemedAssets,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    &UISizing {
+        window_size,
+        top_padding,
+        scale,
+        ..
+    }: &UISizing,
+) {
     let texture_handle = asset_server.load("spritesheets/faces.png");
     let texture_atlas = TextureAtlas::from_grid(
         texture_handle,
         Vec2::splat(FACE_SPRITE_SIZE),
-        5,
+        6,
         3,
         None,
         None,
@@ -355,7 +678,155 @@ fn spawn_buttons(
 
 fn spawn_bomb_display(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    asset_server: &ThemedAssets,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    ui_sizing: &UISizing,
+) {
+    spawn_digit_display(
+        commands,
+        asset_server,
+        texture_atlases,
+        ui_sizing,
+        -0.35,
+        1.0,
+        BombCounterDigit,
+    );
+}
+
+fn spawn_timer_display(
+    commands: &mut Commands,
+    asset_server: &ThemedAssets,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    ui_sizing: &UISizing,
+) {
+    spawn_digit_display(
+        commands,
+        asset_server,
+        texture_atlases,
+        ui_sizing,
+        0.42,
+        1.0,
+        TimerDigit,
+    );
+}
+
+// smaller so it fits between the bomb counter and the faces on easy
+fn spawn_safe_tiles_display(
+    commands: &mut Commands,
+    asset_server: &ThemedAssets,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    ui_sizing: &UISizing,
+) {
+    spawn_digit_display(
+        commands,
+        asset_server,
+        texture_atlases,
+        ui_sizing,
+        -0.2,
+        0.6,
+        SafeTilesDigit,
+    );
+}
+
+// under the timer
+fn spawn_best_time_display(
+    commands: &mut Commands,
+    &UISizing {
+        window_size,
+        top_padding,
+        edge_padding,
+        scale,
+        ..
+    }: &UISizing,
+) {
+    let style = TextStyle {
+        font_size: 8.0 * scale,
+        color: Color::BLACK,
+        ..default()
+    };
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style),
+            text_anchor: Anchor::TopCenter,
+            transform: Transform::from_xyz(
+                (window_size.0 - 2.0 * edge_padding) * 0.42,
+                (window_size.1 - top_padding) / 2.0
+                    - 0.6 * DIGIT_SPRITE_SIZE.1 * scale,
+                1.0,
+            ),
+            ..default()
+        },
+        BestTimeText,
+    ));
+}
+
+// under the bomb counter, shown while the bot plays game after game
+fn spawn_loop_indicator(
+    commands: &mut Commands,
+    &UISizing {
+        window_size,
+        top_padding,
+        edge_padding,
+        scale,
+        ..
+    }: &UISizing,
+) {
+    let style = TextStyle {
+        font_size: 8.0 * scale,
+        color: Color::BLACK,
+        ..default()
+    };
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("loop", style),
+            text_anchor: Anchor::TopCenter,
+            transform: Transform::from_xyz(
+                -(window_size.0 - 2.0 * edge_padding) * 0.35,
+                (window_size.1 - top_padding) / 2.0
+                    - 0.6 * DIGIT_SPRITE_SIZE.1 * scale,
+                1.0,
+            ),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        LoopIndicator,
+    ));
+}
+
+// under the faces
+fn spawn_danger_meter(
+    commands: &mut Commands,
+    &UISizing {
+        window_size,
+        top_padding,
+        scale,
+        ..
+    }: &UISizing,
+) {
+    let style = TextStyle {
+        font_size: 8.0 * scale,
+        color: Color::BLACK,
+        ..default()
+    };
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", style),
+            text_anchor: Anchor::TopCenter,
+            transform: Transform::from_xyz(
+                0.0,
+                (window_size.1 - top_padding) / 2.0
+                    - 0.6 * 1.25 * TILE_SPRITE_SIZE * scale,
+                1.0,
+            ),
+            ..default()
+        },
+        DangerMeter,
+    ));
+}
+
+fn spawn_digit_display(
+    commands: &mut Commands,
+    asset_server: &ThemedAssets,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
     &UISizing {
         window_size,
@@ -364,6 +835,9 @@ fn spawn_bomb_display(
         scale,
         ..
     }: &UISizing,
+    horizontal_offset: f32,
+    size: f32,
+    digit: impl Component + Copy,
 ) {
     let texture_handle = asset_server.load("spritesheets/numbers.png");
     let texture_atlas = TextureAtlas::from_grid(
@@ -377,11 +851,11 @@ fn spawn_bomb_display(
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
     let transform = Transform {
         translation: Vec3::new(
-            -(window_size.0 - 2.0 * edge_padding) * 0.35,
+            (window_size.0 - 2.0 * edge_padding) * horizontal_offset,
             (window_size.1 - top_padding) / 2.0,
             1.0,
         ),
-        scale: Vec3::splat(scale),
+        scale: Vec3::splat(size * scale),
         ..default()
     };
     commands
@@ -398,7 +872,7 @@ fn spawn_bomb_display(
                         ),
                         ..default()
                     },
-                    BombCounterDigit,
+                    digit,
                 );
                 parent.spawn(new_digit);
             }
@@ -407,7 +881,7 @@ fn spawn_bomb_display(
 
 fn spawn_padding(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    asset_server: &ThemedAssets,
     &UISizing {
         window_size,
         board_size,
@@ -473,7 +947,7 @@ fn spawn_padding(
     // connecters
     let mut spawn_connecter = |filename: &str, translation: Vec2| {
         commands.spawn(SpriteBundle {
-            texture: asset_server.load("padding/".to_owned() + filename),
+            texture: asset_server.load(&("padding/".to_owned() + filename)),
             transform: Transform {
                 scale: Vec3::splat(scale),
                 translation: translation.extend(2.0),
@@ -510,7 +984,7 @@ fn spawn_padding(
 
 fn spawn_padding_piece(
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
+    asset_server: &ThemedAssets,
     centre: Vec2,
     horizontal: bool,
     length: f32,
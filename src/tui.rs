@@ -0,0 +1,199 @@
+use crate::agent;
+use crate::board::TilePos;
+use crate::board::{Action, ActionResult, Board, FirstClickPolicy, TileState};
+use crate::Difficulty;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+};
+use crossterm::style::{Color, Print, Stylize};
+use crossterm::terminal::{
+    self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+
+pub const HELP: &str =
+    "arrows move, space uncovers, f flags, b bot move, n new game, q quits";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Step(isize, isize),
+    Uncover,
+    Flag,
+    Bot,
+    NewGame,
+    Quit,
+    Other,
+}
+
+impl Key {
+    pub fn from_event(event: KeyEvent) -> Key {
+        match event.code {
+            KeyCode::Up => Key::Step(0, -1),
+            KeyCode::Down => Key::Step(0, 1),
+            KeyCode::Right => Key::Step(1, 0),
+            KeyCode::Left => Key::Step(-1, 0),
+            KeyCode::Char(' ') | KeyCode::Enter => Key::Uncover,
+            KeyCode::Char('f' | 'F') => Key::Flag,
+            KeyCode::Char('b' | 'B') => Key::Bot,
+            KeyCode::Char('n' | 'N') => Key::NewGame,
+            // raw mode stops ctrl-c from interrupting
+            KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => {
+                Key::Quit
+            }
+            KeyCode::Char('q' | 'Q') => Key::Quit,
+            _ => Key::Other,
+        }
+    }
+
+    /// Blocks until a key is pressed, skipping other terminal events
+    pub fn read() -> io::Result<Key> {
+        loop {
+            if let Event::Key(event) = event::read()? {
+                if event.kind != KeyEventKind::Release {
+                    return Ok(Key::from_event(event));
+                }
+            }
+        }
+    }
+}
+
+/// A board played in the terminal, with the tile the keys act on
+pub struct Game {
+    pub board: Board,
+    pub cursor: TilePos,
+    pub result: ActionResult,
+    first_click: FirstClickPolicy,
+}
+
+impl Game {
+    pub fn new(difficulty: Difficulty, first_click: FirstClickPolicy) -> Game {
+        let board =
+            Board::new(difficulty, None).with_first_click_policy(first_click);
+        let cursor = TilePos {
+            col: board.width() / 2,
+            row: board.height() / 2,
+        };
+        Game {
+            board,
+            cursor,
+            result: ActionResult::Continue,
+            first_click,
+        }
+    }
+
+    /// `false` once the player quits
+    pub fn press(&mut self, key: Key) -> bool {
+        match key {
+            Key::Step(d_col, d_row) => {
+                let TilePos { col, row } = self.cursor;
+                self.cursor = TilePos {
+                    col: col
+                        .saturating_add_signed(d_col)
+                        .min(self.board.width() - 1),
+                    row: row
+                        .saturating_add_signed(d_row)
+                        .min(self.board.height() - 1),
+                };
+            }
+            Key::Uncover => self.apply(Action::uncover(self.cursor)),
+            Key::Flag => self.apply(Action::flag(self.cursor)),
+            Key::Bot if self.result == ActionResult::Continue => {
+                for (action, _) in agent::get_all_actions(&self.board) {
+                    self.apply(action);
+                }
+            }
+            Key::NewGame => {
+                *self = Game::new(self.board.difficulty(), self.first_click);
+            }
+            Key::Quit => return false,
+            _ => {}
+        }
+        true
+    }
+
+    fn apply(&mut self, action: Action) {
+        if self.result == ActionResult::Continue {
+            self.result = self.board.apply_action(action);
+        }
+    }
+
+    /// The whole screen, to be drawn from the top left. Raw mode needs `\r`
+    /// to return to the start of each line.
+    pub fn render(&self) -> String {
+        let status = match self.result {
+            ActionResult::Win => "You won!",
+            ActionResult::Lose => "You lost",
+            ActionResult::Continue => "",
+        };
+        let mut screen =
+            format!("{} bombs left  {status}\r\n", self.board.num_bombs_left());
+        for (pos, state) in self.board.tiles() {
+            if pos.col == 0 && pos.row > 0 {
+                screen.push_str("\r\n");
+            }
+            let tile = format!("{} ", state.symbol()).with(colour(state));
+            if pos == self.cursor {
+                screen.push_str(&tile.reverse().to_string());
+            } else {
+                screen.push_str(&tile.to_string());
+            }
+        }
+        screen.push_str(&format!("\r\n{HELP}\r\n"));
+        screen
+    }
+}
+
+// close to the tile sprites
+fn colour(state: TileState) -> Color {
+    match state {
+        TileState::UncoveredSafe(1) => Color::DarkBlue,
+        TileState::UncoveredSafe(2) => Color::DarkGreen,
+        TileState::UncoveredSafe(3) | TileState::ExplodedBomb => Color::DarkRed,
+        TileState::UncoveredSafe(4) => Color::DarkMagenta,
+        TileState::UncoveredSafe(5) | TileState::Flagged => Color::DarkYellow,
+        TileState::UncoveredSafe(6) => Color::DarkCyan,
+        TileState::Covered => Color::DarkGrey,
+        _ => Color::Reset,
+    }
+}
+
+// restores the terminal however the game ends
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Plays in the terminal until the player quits, without Bevy
+pub fn run(
+    difficulty: Difficulty,
+    first_click: FirstClickPolicy,
+) -> io::Result<()> {
+    let _raw_mode = RawMode::enable()?;
+    let mut game = Game::new(difficulty, first_click);
+    let mut stdout = io::stdout().lock();
+    loop {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            Clear(ClearType::All),
+            Print(game.render())
+        )?;
+        stdout.flush()?;
+        if !game.press(Key::read()?) {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,718 @@
+use itertools::Itertools;
+use minesweeper::agent;
+use minesweeper::agent::deductions;
+use minesweeper::agent::ActionOrigin;
+use minesweeper::board::{
+    Action, ActionResult, ActionType, Board, TilePos, TileState,
+};
+use minesweeper::Difficulty;
+use std::time::Duration;
+
+#[test]
+fn next_action_plays_game_to_completion() {
+    for seed in 0..10 {
+        let mut board = Board::new(Difficulty::Medium, Some(seed));
+        let mut result = ActionResult::Continue;
+        while let Some(action) = agent::next_action(&board) {
+            result = board.apply_action(action);
+        }
+        assert_ne!(result, ActionResult::Continue);
+        assert_eq!(board.result(), result);
+    }
+}
+
+#[test]
+fn next_action_matches_first_of_all_actions() {
+    let mut board = Board::new(Difficulty::Hard, Some(7));
+    for _ in 0..20 {
+        let Some(action) = agent::next_action(&board) else {
+            break;
+        };
+        let (first, _) = agent::get_all_actions(&board).remove(0);
+        assert_eq!(action, first);
+        board.apply_action(action);
+    }
+}
+
+#[test]
+fn deduction_frontier_is_correct() {
+    for seed in 0..5 {
+        let mut board = Board::new(Difficulty::Hard, Some(seed));
+        assert_eq!(agent::deduction_frontier(&board), (vec![], vec![]));
+        board.apply_action(agent::next_action(&board).unwrap());
+        let (safe, bombs) = agent::deduction_frontier(&board);
+        assert!(!safe.is_empty() || !bombs.is_empty());
+        let layout = board.bomb_positions();
+        assert!(safe.iter().all(|pos| !layout.contains(pos)));
+        assert!(bombs.iter().all(|pos| layout.contains(pos)));
+        // nothing is applied
+        assert_eq!(agent::deduction_frontier(&board), (safe, bombs));
+    }
+}
+
+#[test]
+fn cached_deduction_frontier_matches() {
+    for seed in 0..5 {
+        let mut board = Board::new(Difficulty::Hard, Some(seed));
+        let mut cache = agent::SolverCache::default();
+        let mut result = ActionResult::Continue;
+        while result == ActionResult::Continue {
+            assert_eq!(
+                agent::deduction_frontier_cached(&board, &mut cache),
+                agent::deduction_frontier(&board)
+            );
+            result = board.apply_action(agent::next_action(&board).unwrap());
+        }
+    }
+}
+
+#[test]
+fn first_guess_parses_and_opens_there() {
+    let board = Board::new(Difficulty::Hard, Some(1));
+    let pos = |s: &str| s.parse::<agent::FirstGuess>().unwrap().pos(&board);
+    assert_eq!(pos("near-edge"), TilePos { col: 2, row: 8 });
+    assert_eq!(pos("center"), TilePos { col: 15, row: 8 });
+    assert_eq!(pos("corner"), TilePos { col: 0, row: 0 });
+    assert_eq!(pos("4, 5"), TilePos { col: 4, row: 5 });
+    assert_eq!(pos("100,100"), TilePos { col: 29, row: 15 });
+    assert!("middle".parse::<agent::FirstGuess>().is_err());
+}
+
+/// Every move the bot makes solving the board, each with the board it was
+/// made on, and how the game ended
+fn game_moves(
+    board: &Board,
+    style: agent::GuessStyle,
+    budget: agent::GuessBudget,
+) -> (Vec<(Board, Action, ActionOrigin)>, ActionResult) {
+    let mut actions = vec![];
+    let result = agent::solve_cached(
+        &mut board.clone(),
+        &mut agent::SolverCache::default(),
+        agent::FirstGuess::default(),
+        style,
+        budget,
+        agent::BotSkill::default(),
+        |action, origin| actions.push((action, origin)),
+    );
+    // play the moves again to see the board before each one
+    let mut board = board.clone();
+    let moves = actions
+        .into_iter()
+        .map(|(action, origin)| {
+            let before = board.clone();
+            board.apply_action(action);
+            (before, action, origin)
+        })
+        .collect();
+    (moves, result)
+}
+
+#[test]
+fn csp_guesses_match_enumeration() {
+    let mut num_guesses = 0;
+    for seed in 0..10 {
+        let board = Board::new(Difficulty::Hard, Some(seed));
+        let budget = agent::GuessBudget::default();
+        let style = agent::GuessStrategy::Enumerate.into();
+        for (board, action, origin) in game_moves(&board, style, budget).0 {
+            if origin == ActionOrigin::Guessed {
+                num_guesses += 1;
+                let csp = agent::get_all_actions_cached(
+                    &board,
+                    &mut agent::SolverCache::default(),
+                    agent::FirstGuess::default(),
+                    agent::GuessStrategy::Csp.into(),
+                    budget,
+                    agent::BotSkill::default(),
+                );
+                assert_eq!(csp, [(action, origin)]);
+            }
+        }
+    }
+    assert!(num_guesses > 10);
+}
+
+#[test]
+fn tiny_guess_budget_still_guesses_covered_tiles() {
+    let budget = agent::GuessBudget {
+        max_tiles: 1,
+        max_time: None,
+        ..Default::default()
+    };
+    for strategy in [agent::GuessStrategy::Enumerate, agent::GuessStrategy::Csp]
+    {
+        let board = Board::new(Difficulty::Hard, Some(3));
+        let (moves, result) = game_moves(&board, strategy.into(), budget);
+        // the bot never ran out of moves
+        assert_ne!(result, ActionResult::Continue);
+        for (board, action, origin) in moves {
+            if origin == ActionOrigin::Guessed {
+                assert!(board.tile_state(action.pos).covered());
+            }
+        }
+    }
+}
+
+#[test]
+fn progress_tolerance_trades_a_little_safety_for_progress() {
+    let guess = |fen: &str, progress_tolerance| {
+        let style = agent::GuessStyle {
+            progress_tolerance,
+            ..Default::default()
+        };
+        let actions = agent::get_all_actions_cached(
+            &Board::from_fen(fen).unwrap(),
+            &mut agent::SolverCache::default(),
+            agent::FirstGuess::default(),
+            style,
+            agent::GuessBudget::default(),
+            agent::BotSkill::default(),
+        );
+        assert_eq!(actions[0].1, ActionOrigin::Guessed);
+        actions[0].0.pos
+    };
+    // early on, a tile in the open is nearly as safe as the safest next to
+    // the opening, and much more likely to open up more of the board
+    let early = "10/10/10/10/1cbc6/1bab6/1cbc6/10/10/10 10x10 10 117";
+    assert_eq!(guess(early, 0.0), TilePos { col: 0, row: 4 });
+    assert_eq!(guess(early, 0.05), TilePos { col: 1, row: 1 });
+    // late on, the four tiles left are as likely as each other to be bombs,
+    // and the one next to the most numbers settles more
+    let late = "2cbabFbaa/2Fdbcbbaa/bdFdFbaaaa/abbcccbaaa/aaaabFbaaa/\
+                aaaabbcbba/aabccbbFba/aabFFbbbba/aabccbaaaa/aaaaaaaaaa \
+                10x10 10 82";
+    assert_eq!(guess(late, 0.0), TilePos { col: 0, row: 0 });
+    assert_eq!(guess(late, 0.05), TilePos { col: 1, row: 1 });
+}
+
+#[test]
+fn progress_tolerance_still_guesses_covered_tiles() {
+    let style = agent::GuessStyle {
+        progress_tolerance: 0.05,
+        ..Default::default()
+    };
+    for seed in 0..5 {
+        let board = Board::new(Difficulty::Medium, Some(seed));
+        let budget = agent::GuessBudget::default();
+        let (moves, result) = game_moves(&board, style, budget);
+        assert_ne!(result, ActionResult::Continue);
+        for (board, action, origin) in moves {
+            if origin == ActionOrigin::Guessed {
+                assert!(board.tile_state(action.pos).covered());
+            }
+        }
+    }
+}
+
+#[test]
+fn slips_guess_riskier_tiles() {
+    let budget = agent::GuessBudget::default();
+    let guess = |fen: &str, skill| {
+        let actions = agent::get_all_actions_cached(
+            &Board::from_fen(fen).unwrap(),
+            &mut agent::SolverCache::default(),
+            agent::FirstGuess::default(),
+            agent::GuessStyle::default(),
+            budget,
+            agent::BotSkill(skill),
+        );
+        assert_eq!(actions[0].1, ActionOrigin::Guessed);
+        actions[0].0.pos
+    };
+    // no skill always slips, onto a tile more likely a bomb than the best
+    let early = "10/10/10/10/1cbc6/1bab6/1cbc6/10/10/10 10x10 10 117";
+    let probabilities =
+        agent::tile_probabilities(&Board::from_fen(early).unwrap(), budget);
+    assert!(
+        probabilities[&guess(early, 0.0)] > probabilities[&guess(early, 1.0)]
+    );
+    // the four tiles left are each a bomb half the time, so none is worse
+    let late = "2cbabFbaa/2Fdbcbbaa/bdFdFbaaaa/abbcccbaaa/aaaabFbaaa/\
+                aaaabbcbba/aabccbbFba/aabFFbbbba/aabccbaaaa/aaaaaaaaaa \
+                10x10 10 82";
+    assert_eq!(guess(late, 0.0), guess(late, 1.0));
+}
+
+#[test]
+fn unskilled_bot_wins_less_but_repeatably() {
+    let play = |seed, skill| {
+        let mut board = Board::new(Difficulty::Easy, Some(seed));
+        agent::solve_cached(
+            &mut board,
+            &mut agent::SolverCache::default(),
+            agent::FirstGuess::default(),
+            agent::GuessStyle::default(),
+            agent::GuessBudget::default(),
+            agent::BotSkill(skill),
+            |_, _| {},
+        )
+    };
+    let wins = |skill| {
+        (0..20)
+            .filter(|&seed| play(seed, skill) == ActionResult::Win)
+            .count()
+    };
+    assert!(wins(0.0) < wins(1.0));
+    for seed in 0..5 {
+        assert_eq!(play(seed, 0.5), play(seed, 0.5));
+    }
+}
+
+#[test]
+fn parallel_enumeration_matches_sequential() {
+    // every other tile uncovered, so the whole board is one boundary with
+    // plenty of ways to arrange the bombs
+    let mut board = Board::parse_layout(
+        "\
+*..*..*
+.......
+..*...*
+*......
+...*.*.
+.......
+*..*..*",
+    )
+    .unwrap();
+    for (col, row) in [1, 3, 5].into_iter().cartesian_product([1, 3, 5]) {
+        board.apply_action(Action::uncover(TilePos { col, row }));
+    }
+    let sequential = agent::GuessBudget {
+        parallel: false,
+        ..Default::default()
+    };
+    let parallel = agent::GuessBudget {
+        parallel: true,
+        ..Default::default()
+    };
+    let probabilities = agent::tile_probabilities(&board, parallel);
+    assert_eq!(probabilities.len(), 40);
+    assert_eq!(probabilities, agent::tile_probabilities(&board, sequential));
+}
+
+/// Uncovers `start` on the layout and checks the wall of numbers it opens up
+/// needs the subset bounds to find `bombs`, after which flagging them makes
+/// `safe` trivial
+fn check_wall(
+    layout: &str,
+    start: TilePos,
+    safe: &[TilePos],
+    bombs: &[TilePos],
+) {
+    let mut board = Board::parse_layout(layout).unwrap();
+    board.apply_action(Action::uncover(start));
+    assert!(agent::get_trivial_actions(&board).is_empty());
+    let mut flags =
+        agent::deduplicate(deductions::get_non_trivial_actions(&board));
+    flags.sort_by_key(|action| action.pos);
+    let expected = bombs
+        .iter()
+        .map(|&pos| Action::flag(pos))
+        .collect::<Vec<_>>();
+    assert_eq!(flags, expected);
+    for action in flags {
+        board.apply_action(action);
+    }
+    let mut uncovers = agent::deduplicate(agent::get_trivial_actions(&board));
+    uncovers.sort_by_key(|action| action.pos);
+    let expected = safe
+        .iter()
+        .map(|&pos| Action::uncover(pos))
+        .collect::<Vec<_>>();
+    assert_eq!(uncovers, expected);
+}
+
+#[test]
+fn deduces_one_two_one_wall() {
+    check_wall(
+        "*.*\n...\n...",
+        TilePos { col: 1, row: 2 },
+        &[TilePos { col: 1, row: 0 }],
+        &[TilePos { col: 0, row: 0 }, TilePos { col: 2, row: 0 }],
+    );
+}
+
+#[test]
+fn deduces_one_two_two_one_wall() {
+    check_wall(
+        ".**.\n....\n....",
+        TilePos { col: 0, row: 2 },
+        &[TilePos { col: 0, row: 0 }, TilePos { col: 3, row: 0 }],
+        &[TilePos { col: 1, row: 0 }, TilePos { col: 2, row: 0 }],
+    );
+}
+
+#[test]
+fn explains_each_deduction() {
+    let mut board = Board::parse_layout("*.*\n...\n...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 1, row: 2 }));
+    let explained = deductions::get_non_trivial_actions_explained(&board);
+    let actions = explained.iter().map(|&(action, _)| action).collect_vec();
+    assert_eq!(actions, deductions::get_non_trivial_actions(&board));
+    assert_eq!(
+        explained[1].1,
+        "(1, 1) needs 2 bombs; {(1, 0), (2, 0)} can hold at most 1, so \
+         {(0, 0)} must be bombs"
+    );
+}
+
+#[test]
+fn bombs_left_on_the_boundary_clear_the_rest() {
+    // the 1 needs the only bomb, so the tiles past it can't hold one
+    let mut board = Board::parse_layout("...*...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 5, row: 0 }));
+    let explained = deductions::get_non_trivial_actions_explained(&board);
+    let actions = explained.iter().map(|&(action, _)| action).collect_vec();
+    let expected = (0..3)
+        .map(|col| Action::uncover(TilePos { col, row: 0 }))
+        .collect_vec();
+    assert_eq!(actions, expected);
+    assert_eq!(
+        explained[0].1,
+        "{(3, 0)} must hold at least 1 bombs, all that are left, so \
+         {(0, 0), (1, 0), (2, 0)} must be safe"
+    );
+    // another bomb could be anywhere past it
+    let mut board = Board::parse_layout("*..*...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 5, row: 0 }));
+    assert!(deductions::get_non_trivial_actions(&board).is_empty());
+}
+
+#[test]
+fn solver_timings_show_share_of_each_stage() {
+    let mut timings = agent::SolverTimings {
+        trivial: Duration::from_millis(100),
+        deduction: Duration::from_millis(200),
+        guessing: Duration::ZERO,
+    };
+    timings.merge(&agent::SolverTimings {
+        guessing: Duration::from_millis(700),
+        ..Default::default()
+    });
+    assert_eq!(
+        timings.to_string(),
+        "70% guessing, 20% deduction, 10% trivial deduction"
+    );
+}
+
+#[test]
+fn information_gain_guesses_are_nearly_safest() {
+    let mut num_guesses = 0;
+    for seed in 0..10 {
+        let board = Board::new(Difficulty::Hard, Some(seed));
+        let budget = agent::GuessBudget::default();
+        let style = agent::GuessStrategy::InformationGain.into();
+        for (board, action, origin) in game_moves(&board, style, budget).0 {
+            if origin == ActionOrigin::Guessed {
+                num_guesses += 1;
+                let probabilities = agent::tile_probabilities(&board, budget);
+                let safest = probabilities
+                    .values()
+                    .copied()
+                    .fold(f64::INFINITY, f64::min);
+                assert!(probabilities[&action.pos] <= safest + 0.005 + 1e-9);
+            }
+        }
+    }
+    assert!(num_guesses > 10);
+}
+
+#[test]
+fn solve_wins_no_guess_boards() {
+    for seed in 0..3 {
+        let mut board =
+            agent::generate_no_guess(Difficulty::Hard, Some(seed)).unwrap();
+        assert_eq!(agent::solve(&mut board), ActionResult::Win);
+        assert_eq!(board.result(), ActionResult::Win);
+    }
+}
+
+/// Solves the board, failing rather than hanging if it takes more moves than
+/// there are tiles, as each move should change a different tile
+fn solve_counting_moves(
+    board: &mut Board,
+) -> (ActionResult, Vec<ActionOrigin>) {
+    let num_tiles = board.width() * board.height();
+    let mut origins = vec![];
+    let result = agent::solve_cached(
+        board,
+        &mut agent::SolverCache::default(),
+        agent::FirstGuess::default(),
+        agent::GuessStyle::default(),
+        agent::GuessBudget::default(),
+        agent::BotSkill::default(),
+        |_, origin| {
+            origins.push(origin);
+            assert!(origins.len() <= num_tiles, "solver is going in circles");
+        },
+    );
+    (result, origins)
+}
+
+#[test]
+fn solve_wins_easy_no_guess_boards_within_a_move_per_tile() {
+    for seed in 0..10 {
+        let mut board =
+            agent::generate_no_guess(Difficulty::Easy, Some(seed)).unwrap();
+        let (result, origins) = solve_counting_moves(&mut board);
+        assert_eq!(result, ActionResult::Win);
+        assert!(!origins.contains(&ActionOrigin::Guessed));
+    }
+}
+
+#[test]
+fn solve_guesses_its_way_past_a_coinflip() {
+    // nothing tells the two covered tiles apart, so the bot has to guess
+    let mut board = Board::parse_layout("*...\n....").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 3, row: 0 }));
+    assert!(agent::get_deduced_actions(&board).is_empty());
+    let (result, origins) = solve_counting_moves(&mut board);
+    assert_ne!(result, ActionResult::Continue);
+    assert_eq!(origins[0], ActionOrigin::Guessed);
+}
+
+#[test]
+fn cache_keeps_the_odds_of_each_guess() {
+    let mut board = Board::parse_layout("*...\n....").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 3, row: 0 }));
+    let mut cache = agent::SolverCache::default();
+    agent::solve_cached(
+        &mut board,
+        &mut cache,
+        agent::FirstGuess::default(),
+        agent::GuessStyle::default(),
+        agent::GuessBudget::default(),
+        agent::BotSkill::default(),
+        |_, _| {},
+    );
+    assert_eq!(cache.guess_odds(), [Some(0.5)]);
+}
+
+#[test]
+fn cached_trivial_actions_match_full_scan() {
+    for seed in 0..5 {
+        let mut board = Board::new(Difficulty::Hard, Some(seed));
+        let mut cache = agent::SolverCache::default();
+        let mut result = ActionResult::Continue;
+        while result == ActionResult::Continue {
+            let actions = agent::get_all_actions_cached(
+                &board,
+                &mut cache,
+                agent::FirstGuess::default(),
+                agent::GuessStyle::default(),
+                agent::GuessBudget::default(),
+                agent::BotSkill::default(),
+            );
+            // the cache only keeps trivial deductions near changed tiles
+            assert_eq!(actions, agent::get_all_actions(&board));
+            for (action, _) in actions {
+                result = board.apply_action(action);
+                if result != ActionResult::Continue {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn solving_without_flags_ends_the_same_way() {
+    for seed in 0..5 {
+        let mut flagged = Board::new(Difficulty::Hard, Some(seed));
+        let mut unflagged = flagged.clone();
+        let result = agent::solve(&mut flagged);
+        let mut num_flags = 0;
+        let result_without_flags = agent::solve_without_flags(
+            &mut unflagged,
+            &mut agent::SolverCache::default(),
+            agent::FirstGuess::default(),
+            agent::GuessStyle::default(),
+            agent::GuessBudget::default(),
+            agent::BotSkill::default(),
+            |action, _| {
+                num_flags +=
+                    (action.action_type != ActionType::Uncover) as usize
+            },
+        );
+        assert_eq!(result_without_flags, result);
+        assert_eq!(num_flags, 0);
+        if result == ActionResult::Lose {
+            assert_eq!(unflagged.num_bombs_left(), 99);
+        }
+    }
+}
+
+#[test]
+fn heatmap_has_a_square_per_tile() {
+    let mut board = Board::new(Difficulty::Easy, Some(4));
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 4 }));
+    let path = std::env::temp_dir().join("minesweeper_heatmap_test.png");
+    agent::save_probability_heatmap(&board, &path).unwrap();
+    let image = image::open(&path).unwrap().into_rgb8();
+    std::fs::remove_file(&path).unwrap();
+    let size = agent::HEATMAP_TILE_PIXELS;
+    assert_eq!(image.dimensions(), (10 * size, 10 * size));
+    let pixel = |pos: TilePos| {
+        // the last pixel of the tile, so each square is filled
+        let x = (pos.col as u32 + 1) * size - 1;
+        let y = (pos.row as u32 + 1) * size - 1;
+        image.get_pixel(x, y).0
+    };
+    assert_eq!(pixel(TilePos { col: 4, row: 4 }), [192, 192, 192]);
+    let probabilities =
+        agent::tile_probabilities(&board, agent::GuessBudget::default());
+    for (&pos, &bomb_prob) in &probabilities {
+        let red = (bomb_prob * 255.0).round() as u8;
+        assert_eq!(pixel(pos), [red, 255 - red, 0]);
+    }
+}
+
+#[test]
+fn coinflips_need_exactly_one_bomb() {
+    let budget = agent::GuessBudget::default();
+    // the two covered tiles share every number, and only one bomb is left
+    let mut board = Board::parse_layout("*..\n...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 2, row: 0 }));
+    assert_eq!(
+        agent::detect_coinflips(&board, budget),
+        Some(vec![(
+            TilePos { col: 0, row: 0 },
+            TilePos { col: 0, row: 1 }
+        )])
+    );
+    // too many tiles to enumerate says so, rather than finding nothing
+    let tiny = agent::GuessBudget {
+        max_tiles: 1,
+        ..budget
+    };
+    assert_eq!(agent::detect_coinflips(&board, tiny), None);
+    // every tile around the 4 is a bomb half the time, but any two can be
+    let mut board = Board::parse_layout("*.*\n...\n*.*").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 1, row: 1 }));
+    assert_eq!(agent::detect_coinflips(&board, budget), Some(vec![]));
+}
+
+#[test]
+fn expected_guesses_counts_forced_guesses() {
+    // whichever tile the bomb is under, one guess settles it
+    let mut board = Board::parse_layout("*...\n....").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 3, row: 0 }));
+    assert_eq!(agent::expected_guesses(&board, 20), 1.0);
+    // the numbers give every bomb away, wherever they're drawn
+    let mut board =
+        Board::parse_layout("*....\n.....\n..*..\n.....\n....*").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    assert_eq!(agent::expected_guesses(&board, 20), 0.0);
+    // the same board always draws the same layouts
+    let mut board = Board::new(Difficulty::Medium, Some(3));
+    board.apply_action(agent::next_action(&board).unwrap());
+    let expected = agent::expected_guesses(&board, 10);
+    assert!(expected.is_finite() && expected >= 0.0);
+    assert_eq!(agent::expected_guesses(&board, 10), expected);
+}
+
+#[test]
+fn odds_cover_a_component_of_over_100_tiles() {
+    // a row of 1s over a covered row, every tile of which shares a number
+    let width = 102;
+    let top = ".".repeat(width);
+    let bottom = (0..width)
+        .map(|col| if col % 3 == 1 { '*' } else { '.' })
+        .collect::<String>();
+    let mut board = Board::parse_layout(&format!("{top}\n{bottom}")).unwrap();
+    for col in 0..width {
+        board.apply_action(Action::uncover(TilePos { col, row: 0 }));
+    }
+    let probabilities =
+        agent::tile_probabilities(&board, agent::GuessBudget::default());
+    assert_eq!(probabilities.len(), width);
+    // every covered tile is on the boundary, so the odds add up to the bombs
+    let expected_bombs: f64 = probabilities.values().sum();
+    assert!((expected_bombs - board.num_bombs_left() as f64).abs() < 1e-6);
+    // layouts are drawn from the same counts
+    assert!(agent::expected_guesses(&board, 1).is_finite());
+}
+
+#[test]
+fn agent_flags_question_marked_bombs() {
+    let mut board =
+        Board::parse_layout("*....\n.....\n..*..\n.....\n....*").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    for pos in board.bomb_positions() {
+        // a flag, then a question mark
+        board.apply_action(Action::flag(pos));
+        board.apply_action(Action::flag(pos));
+    }
+    // flagged directly, rather than moved on from the question mark
+    let bomb = TilePos { col: 2, row: 2 };
+    let actions = agent::get_all_actions(&board);
+    let bomb_actions = actions
+        .iter()
+        .filter(|(action, _)| action.pos == bomb)
+        .map(|&(action, _)| action)
+        .collect::<Vec<_>>();
+    assert_eq!(bomb_actions, [Action::set_flag(bomb)]);
+    for (action, _) in actions {
+        board.apply_action(action);
+    }
+    assert_eq!(board.tile_state(bomb), TileState::Flagged);
+    assert_eq!(board.num_bombs_left(), 2);
+}
+
+#[test]
+fn solving_without_flags_sees_past_question_marks() {
+    let mut board =
+        Board::parse_layout("*....\n.....\n..*..\n.....\n....*").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    for pos in board.bomb_positions() {
+        board.apply_action(Action::flag(pos));
+        board.apply_action(Action::flag(pos));
+    }
+    let result = agent::solve_without_flags(
+        &mut board,
+        &mut agent::SolverCache::default(),
+        agent::FirstGuess::default(),
+        agent::GuessStyle::default(),
+        agent::GuessBudget::default(),
+        agent::BotSkill::default(),
+        |_, origin| assert_ne!(origin, ActionOrigin::Guessed),
+    );
+    assert_eq!(result, ActionResult::Win);
+}
+
+#[test]
+fn flags_are_deduced_only_from_the_numbers() {
+    // the 1 has a single covered neighbour, so its flag is certain
+    let mut board = Board::parse_layout("*..").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 2, row: 0 }));
+    board.apply_action(Action::flag(TilePos { col: 0, row: 0 }));
+    assert!(agent::flags_are_deduced(&board, TilePos { col: 1, row: 0 }));
+    // the bomb could be either covered tile, and this flag has it wrong
+    let mut board = Board::parse_layout("*..\n...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 2, row: 0 }));
+    let number = TilePos { col: 1, row: 0 };
+    board.apply_action(Action::flag(TilePos { col: 0, row: 1 }));
+    assert_eq!(
+        board.chord_preview(number),
+        Some(vec![TilePos { col: 0, row: 0 }])
+    );
+    assert!(!agent::flags_are_deduced(&board, number));
+}
+
+#[test]
+fn avoiding_5050s_only_moves_seeds_forward() {
+    let first = TilePos { col: 3, row: 8 };
+    let mut num_moved = 0;
+    for seed in 0..10 {
+        let mut plain = Board::new(Difficulty::Hard, Some(seed));
+        let mut fair = Board::new(Difficulty::Hard, Some(seed));
+        agent::avoid_5050(&mut fair, first);
+        plain.apply_action(Action::uncover(first));
+        fair.apply_action(Action::uncover(first));
+        assert!(fair.seed() >= plain.seed());
+        if fair.seed() > plain.seed() {
+            num_moved += 1;
+        }
+    }
+    assert!(num_moved > 0);
+}
@@ -0,0 +1,964 @@
+use minesweeper::agent;
+use minesweeper::board::{
+    Action, ActionResult, Adjacency, Board, BoardError, FirstClickPolicy,
+    ParseError, TilePos, TileState, Topology, WinCondition,
+};
+use minesweeper::Difficulty;
+use rand::rngs::{mock::StepRng, StdRng};
+use rand::SeedableRng;
+
+fn covered_tiles(board: &Board) -> Vec<TilePos> {
+    (0..board.height())
+        .flat_map(|row| (0..board.width()).map(move |col| TilePos { col, row }))
+        .filter(|&pos| board.tile_state(pos) == TileState::Covered)
+        .collect()
+}
+
+// uncover each covered tile on a copy of the board to find out what's there
+fn find_tile(board: &Board, bomb: bool) -> TilePos {
+    covered_tiles(board)
+        .into_iter()
+        .find(|&pos| {
+            let mut copy = board.clone();
+            let lost =
+                copy.apply_action(Action::uncover(pos)) == ActionResult::Lose;
+            lost == bomb
+        })
+        .unwrap()
+}
+
+fn assert_round_trip(board: &Board) {
+    let fen = board.to_fen().unwrap();
+    let loaded = Board::from_fen(&fen).unwrap();
+    assert_eq!(loaded.tile_states(), board.tile_states());
+    assert_eq!(loaded.width(), board.width());
+    assert_eq!(loaded.height(), board.height());
+    assert_eq!(loaded.seed(), board.seed());
+    assert_eq!(loaded.num_bombs_total(), board.num_bombs_total());
+    assert_eq!(loaded.num_bombs_left(), board.num_bombs_left());
+    assert_eq!(loaded.first_uncovered(), board.first_uncovered());
+    assert_eq!(loaded.difficulty(), board.difficulty());
+    assert_eq!(loaded.to_fen().unwrap(), fen);
+}
+
+fn started_board() -> Board {
+    let mut board = Board::new(Difficulty::Medium, Some(3));
+    board.apply_action(Action::uncover(TilePos { col: 8, row: 8 }));
+    board
+}
+
+#[test]
+fn fen_round_trips_fresh_board() {
+    let board = Board::new(Difficulty::Hard, Some(42));
+    assert_eq!(
+        board.to_fen().unwrap().split(' ').next(),
+        Some("30/".repeat(16).trim_end_matches('/'))
+    );
+    assert_round_trip(&board);
+}
+
+#[test]
+fn fen_round_trips_flags_and_questions() {
+    let mut board = started_board();
+    let bomb = find_tile(&board, true);
+    board.apply_action(Action::flag(bomb));
+    let safe = find_tile(&board, false);
+    board.apply_action(Action::flag(safe));
+    board.apply_action(Action::flag(safe));
+    assert_eq!(board.tile_state(bomb), TileState::Flagged);
+    assert_eq!(board.tile_state(safe), TileState::Questioned);
+    assert_round_trip(&board);
+}
+
+#[test]
+fn fen_round_trips_lost_board() {
+    let mut board = started_board();
+    let safe = find_tile(&board, false);
+    board.apply_action(Action::flag(safe));
+    let bomb = find_tile(&board, true);
+    assert!(board.apply_action(Action::uncover(bomb)) == ActionResult::Lose);
+    let states = board.tile_states();
+    assert!(states.contains(&TileState::ExplodedBomb));
+    assert!(states.contains(&TileState::UncoveredBomb));
+    assert!(states.contains(&TileState::Misflagged));
+    assert!(states
+        .iter()
+        .any(|x| matches!(x, TileState::UncoveredSafe(_))));
+    assert_round_trip(&board);
+}
+
+#[test]
+fn fen_round_trips_custom_board() {
+    let mut board = Board::with_dimensions(7, 5, 6, Some(9)).unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 3, row: 2 }));
+    assert_round_trip(&board);
+}
+
+#[test]
+fn fen_rejects_bad_input() {
+    let fen = started_board().to_fen().unwrap();
+    let (rows, rest) = fen.split_once(' ').unwrap();
+    assert_eq!(
+        Board::from_fen(rows).err(),
+        Some(ParseError::MissingField("dimensions"))
+    );
+    assert_eq!(
+        Board::from_fen(&format!("Z{} {rest}", &rows[1..])).err(),
+        Some(ParseError::InvalidTile('Z'))
+    );
+    assert_eq!(
+        Board::from_fen(&format!("1{rows} {rest}")).err(),
+        Some(ParseError::WrongDimensions)
+    );
+    // a run longer than the board is turned down before it's allocated
+    assert_eq!(
+        Board::from_fen("99999999999999 3x3 1 0").err(),
+        Some(ParseError::WrongDimensions)
+    );
+    let too_many_tiles = format!("1 {}x2 1 0", usize::MAX);
+    assert_eq!(
+        Board::from_fen(&too_many_tiles).err(),
+        Some(ParseError::WrongDimensions)
+    );
+    // a different seed puts the bombs somewhere else
+    let fields: Vec<_> = rest.split(' ').collect();
+    let seed: u64 = fields[2].parse().unwrap();
+    let wrong_seed = format!("{rows} {} {} {}", fields[0], fields[1], seed + 1);
+    assert!(matches!(
+        Board::from_fen(&wrong_seed).err(),
+        Some(ParseError::InconsistentLayout(_))
+    ));
+    let unknown_topology = fen.replace("bounded", "spherical");
+    assert_eq!(
+        Board::from_fen(&unknown_topology).err(),
+        Some(ParseError::UnknownSetting("spherical".to_owned()))
+    );
+}
+
+#[test]
+fn fen_round_trips_settings() {
+    let mut board = Board::with_dimensions(9, 9, 10, Some(4))
+        .unwrap()
+        .with_first_click_policy(FirstClickPolicy::SafeCell)
+        .with_topology(Topology::Toroidal)
+        .with_adjacency(Adjacency::Knight)
+        .with_win_condition(WinCondition::FlagAllMines)
+        .with_strict_flags(true);
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 4 }));
+    assert_round_trip(&board);
+    let loaded = Board::from_fen(&board.to_fen().unwrap()).unwrap();
+    assert_eq!(loaded.first_click_policy(), FirstClickPolicy::SafeCell);
+    assert_eq!(loaded.topology(), Topology::Toroidal);
+    assert_eq!(loaded.adjacency(), Adjacency::Knight);
+    assert_eq!(loaded.win_condition(), WinCondition::FlagAllMines);
+    assert!(loaded.strict_flags());
+}
+
+#[test]
+fn fen_round_trips_placed_bombs() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    board.apply_action(Action::uncover(find_tile(&board, false)));
+    assert_round_trip(&board);
+    let loaded = Board::from_fen(&board.to_fen().unwrap()).unwrap();
+    assert!(loaded.fixed_layout());
+    assert_eq!(loaded.bomb_positions(), board.bomb_positions());
+
+    let mut rotated = started_board();
+    rotated.rotate_90();
+    assert!(rotated.fixed_layout());
+    assert_round_trip(&rotated);
+    let loaded = Board::from_fen(&rotated.to_fen().unwrap()).unwrap();
+    assert_eq!(loaded.bomb_positions(), rotated.bomb_positions());
+}
+
+#[test]
+fn fen_refuses_broken_board() {
+    // changing the neighbours mid game leaves the numbers wrong
+    let board = started_board().with_adjacency(Adjacency::VonNeumann);
+    assert!(matches!(
+        board.to_fen(),
+        Err(BoardError::WrongNumber { .. })
+    ));
+}
+
+#[test]
+fn first_click_policy_controls_first_tile() {
+    let pos = TilePos { col: 4, row: 4 };
+    let mut numbered_first_clicks = 0;
+    for seed in 0..20 {
+        let mut region = Board::new(Difficulty::Hard, Some(seed));
+        assert_eq!(
+            region.apply_action(Action::uncover(pos)),
+            ActionResult::Continue
+        );
+        assert_eq!(region.tile_state(pos), TileState::UncoveredSafe(0));
+
+        let mut cell = Board::new(Difficulty::Hard, Some(seed))
+            .with_first_click_policy(FirstClickPolicy::SafeCell);
+        assert_eq!(
+            cell.apply_action(Action::uncover(pos)),
+            ActionResult::Continue
+        );
+        if cell.tile_state(pos) != TileState::UncoveredSafe(0) {
+            numbered_first_clicks += 1;
+        }
+    }
+    assert!(numbered_first_clicks > 0);
+}
+
+const LAYOUT: &str = "\
+*....
+.....
+..*..
+.....
+....*
+";
+
+#[test]
+fn layout_places_bombs_exactly() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    assert_eq!((board.width(), board.height()), (5, 5));
+    assert_eq!(board.num_bombs_total(), 3);
+    let corner = TilePos { col: 4, row: 0 };
+    assert_eq!(
+        board.apply_action(Action::uncover(corner)),
+        ActionResult::Continue
+    );
+    assert_eq!(board.tile_state(corner), TileState::UncoveredSafe(0));
+    assert_eq!(
+        board.tile_state(TilePos { col: 1, row: 1 }),
+        TileState::UncoveredSafe(2)
+    );
+    // restarting keeps the same bombs
+    board.reset(None);
+    assert_eq!(
+        board.apply_action(Action::uncover(TilePos { col: 0, row: 0 })),
+        ActionResult::Lose
+    );
+}
+
+#[test]
+fn num_bombs_around_counts_true_neighbours() {
+    let board = Board::parse_layout(LAYOUT).unwrap();
+    let count = |col, row| board.num_bombs_around(TilePos { col, row });
+    // corners
+    assert_eq!(count(0, 0), 0);
+    assert_eq!(count(4, 0), 0);
+    assert_eq!(count(0, 4), 0);
+    assert_eq!(count(4, 4), 0);
+    // edges
+    assert_eq!(count(1, 0), 1);
+    assert_eq!(count(0, 1), 1);
+    assert_eq!(count(4, 3), 1);
+    assert_eq!(count(2, 4), 0);
+    // interior, where the bomb in the middle doesn't count itself
+    assert_eq!(count(1, 1), 2);
+    assert_eq!(count(3, 3), 2);
+    assert_eq!(count(2, 2), 0);
+}
+
+#[test]
+fn neighbours_stay_on_the_grid() {
+    let count = |col, row| TilePos { col, row }.neighbours(4, 3).len();
+    // corners
+    assert_eq!(count(0, 0), 3);
+    assert_eq!(count(3, 2), 3);
+    // edges
+    assert_eq!(count(1, 0), 5);
+    assert_eq!(count(0, 1), 5);
+    assert_eq!(count(3, 1), 5);
+    // interior
+    assert_eq!(count(1, 1), 8);
+    assert_eq!(count(2, 1), 8);
+    let board = Board::new(Difficulty::Easy, Some(0));
+    let pos = TilePos { col: 0, row: 4 };
+    assert_eq!(board.neighbours(pos), pos.neighbours(9, 9));
+}
+
+#[test]
+fn toroidal_boards_wrap_around_the_edges() {
+    let board = Board::parse_layout(LAYOUT)
+        .unwrap()
+        .with_topology(Topology::Toroidal);
+    for (pos, _) in board.tiles() {
+        assert_eq!(board.neighbours(pos).len(), 8);
+    }
+    let corner = TilePos { col: 0, row: 0 };
+    assert!(board
+        .neighbours(corner)
+        .contains(&TilePos { col: 4, row: 4 }));
+    assert!(board
+        .neighbours(corner)
+        .contains(&TilePos { col: 4, row: 1 }));
+    // the bomb in the opposite corner now counts
+    assert_eq!(board.num_bombs_around(TilePos { col: 4, row: 0 }), 2);
+    assert_eq!(board.num_bombs_around(TilePos { col: 1, row: 0 }), 1);
+    // grids too small to wrap don't list a tile twice
+    let pos = TilePos { col: 0, row: 1 };
+    assert_eq!(pos.wrapping_neighbours(2, 3).len(), 5);
+    let pos = TilePos { col: 0, row: 0 };
+    assert_eq!(pos.wrapping_neighbours(1, 1).len(), 0);
+}
+
+#[test]
+fn toroidal_boards_are_played_to_the_end() {
+    for seed in 0..5 {
+        let mut board = Board::new(Difficulty::Medium, Some(seed))
+            .with_topology(Topology::Toroidal);
+        let result = agent::solve(&mut board);
+        assert_ne!(result, ActionResult::Continue);
+        assert_eq!(board.validate(), Ok(()));
+    }
+}
+
+#[test]
+fn adjacency_picks_the_neighbours() {
+    let board = Board::parse_layout(LAYOUT)
+        .unwrap()
+        .with_adjacency(Adjacency::VonNeumann);
+    let centre = TilePos { col: 2, row: 2 };
+    assert_eq!(board.neighbours(centre).len(), 4);
+    assert_eq!(board.neighbours(TilePos { col: 0, row: 0 }).len(), 2);
+    // the bombs in the corner and middle are diagonal, so no longer counted
+    assert_eq!(board.num_bombs_around(TilePos { col: 1, row: 1 }), 0);
+    assert_eq!(board.num_bombs_around(TilePos { col: 2, row: 1 }), 1);
+    let board = board.with_adjacency(Adjacency::Knight);
+    assert_eq!(board.neighbours(centre).len(), 8);
+    assert_eq!(board.num_bombs_around(TilePos { col: 1, row: 2 }), 1);
+    assert_eq!(board.num_bombs_around(TilePos { col: 2, row: 1 }), 1);
+    // wrapping, a knight's move from the corner reaches the far side
+    let board = board.with_topology(Topology::Toroidal);
+    assert!(board
+        .neighbours(TilePos { col: 0, row: 0 })
+        .contains(&TilePos { col: 3, row: 4 }));
+}
+
+#[test]
+fn other_adjacencies_are_played_to_the_end() {
+    for adjacency in [Adjacency::VonNeumann, Adjacency::Knight] {
+        for seed in 0..5 {
+            let mut board = Board::new(Difficulty::Medium, Some(seed))
+                .with_adjacency(adjacency);
+            let result = agent::solve(&mut board);
+            assert_ne!(result, ActionResult::Continue);
+            assert_eq!(board.validate(), Ok(()));
+        }
+    }
+}
+
+#[test]
+fn layout_rejects_bad_grids() {
+    let ragged = Board::parse_layout("..*\n.\n...");
+    assert_eq!(ragged.err(), Some(ParseError::WrongDimensions));
+    let unknown = Board::parse_layout("..*\n.x.");
+    assert_eq!(unknown.err(), Some(ParseError::InvalidTile('x')));
+    assert_eq!(
+        Board::parse_layout("\n").err(),
+        Some(ParseError::WrongDimensions)
+    );
+    let off_board = TilePos { col: 3, row: 0 };
+    assert_eq!(
+        Board::from_layout(3, 3, &[off_board]).err(),
+        Some(BoardError::BombOffBoard(off_board))
+    );
+}
+
+#[test]
+fn layout_counts_repeated_bombs_once() {
+    let bomb = TilePos { col: 0, row: 0 };
+    // two bombs would leave no safe tile, one doesn't
+    let board = Board::from_layout(2, 1, &[bomb, bomb]).unwrap();
+    assert_eq!(board.num_bombs_total(), 1);
+    assert_eq!(board.num_bombs_left(), 1);
+    assert_eq!(board.bomb_positions(), vec![bomb]);
+}
+
+#[test]
+fn unsafe_first_click_can_lose() {
+    let first = TilePos { col: 4, row: 4 };
+    let mut num_losses = 0;
+    for seed in 0..50 {
+        let mut board = Board::new(Difficulty::Easy, Some(seed))
+            .with_first_click_policy(FirstClickPolicy::Unsafe);
+        let bombs = board.bomb_positions();
+        assert!(board.seed_is_playable(first));
+        let result = board.apply_action(Action::uncover(first));
+        // the bombs stay where they were dealt
+        assert_eq!(board.seed(), seed);
+        assert_eq!(board.bomb_positions(), bombs);
+        assert_eq!(result == ActionResult::Lose, bombs.contains(&first));
+        num_losses += (result == ActionResult::Lose) as usize;
+    }
+    assert!(num_losses > 0);
+}
+
+#[test]
+fn playable_seed_is_kept_by_first_click() {
+    let first = TilePos { col: 4, row: 4 };
+    for policy in [FirstClickPolicy::SafeCell, FirstClickPolicy::SafeRegion] {
+        for seed in 0..20 {
+            let mut board = Board::new(Difficulty::Easy, Some(seed))
+                .with_first_click_policy(policy);
+            let playable = board.seed_is_playable(first);
+            board.apply_action(Action::uncover(first));
+            assert_eq!(board.seed() == seed, playable);
+            assert!(board.seed_is_playable(first));
+        }
+    }
+}
+
+#[test]
+fn safe_tiles_remaining_counts_down_to_win() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    assert_eq!(board.safe_tiles_remaining(), 22);
+    // flagging a safe tile doesn't count as clearing it
+    board.apply_action(Action::flag(TilePos { col: 1, row: 0 }));
+    assert_eq!(board.safe_tiles_remaining(), 22);
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    let covered = covered_tiles(&board).len();
+    assert_eq!(board.safe_tiles_remaining(), covered + 1 - 3);
+    for pos in covered_tiles(&board) {
+        if !board.bomb_positions().contains(&pos) {
+            board.apply_action(Action::uncover(pos));
+        }
+    }
+    board.apply_action(Action::flag(TilePos { col: 1, row: 0 }));
+    board.apply_action(Action::flag(TilePos { col: 1, row: 0 }));
+    assert_eq!(
+        board.apply_action(Action::uncover(TilePos { col: 1, row: 0 })),
+        ActionResult::Win
+    );
+    assert_eq!(board.safe_tiles_remaining(), 0);
+}
+
+#[test]
+fn true_bombs_remaining_ignores_wrong_flags() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    for col in 1..5 {
+        board.apply_action(Action::flag(TilePos { col, row: 0 }));
+    }
+    assert_eq!(board.num_bombs_left(), -1);
+    assert_eq!(board.true_bombs_remaining(), 3);
+    board.apply_action(Action::flag(TilePos { col: 0, row: 0 }));
+    assert_eq!(board.num_bombs_left(), -2);
+    assert_eq!(board.true_bombs_remaining(), 2);
+}
+
+#[test]
+fn bomb_positions_match_layout() {
+    let board = started_board();
+    let bombs = board.bomb_positions();
+    assert_eq!(bombs.len(), board.num_bombs_total());
+    for pos in covered_tiles(&board) {
+        let mut copy = board.clone();
+        let lost =
+            copy.apply_action(Action::uncover(pos)) == ActionResult::Lose;
+        assert_eq!(lost, bombs.contains(&pos));
+    }
+}
+
+fn bomb_counts(board: &Board) -> Vec<usize> {
+    let bombs = board.bomb_positions();
+    let mut counts = (0..board.height())
+        .flat_map(|row| (0..board.width()).map(move |col| TilePos { col, row }))
+        .filter(|pos| !bombs.contains(pos))
+        .map(|pos| {
+            board
+                .neighbours(pos)
+                .iter()
+                .filter(|neighbour| bombs.contains(neighbour))
+                .count()
+        })
+        .collect::<Vec<_>>();
+    counts.sort();
+    counts
+}
+
+fn assert_consistent(board: &Board) {
+    for row in 0..board.height() {
+        for col in 0..board.width() {
+            let pos = TilePos { col, row };
+            if let TileState::UncoveredSafe(n) = board.tile_state(pos) {
+                let bombs = board.bomb_positions();
+                let count = board
+                    .neighbours(pos)
+                    .iter()
+                    .filter(|neighbour| bombs.contains(neighbour))
+                    .count();
+                assert_eq!(n as usize, count);
+            }
+        }
+    }
+}
+
+#[test]
+fn rotating_four_times_restores_board() {
+    let board = started_board();
+    let mut rotated = Board::from_fen(&board.to_fen().unwrap()).unwrap();
+    let mut hard = Board::new(Difficulty::Hard, Some(1));
+    hard.rotate_90();
+    assert_eq!((hard.width(), hard.height()), (16, 30));
+    assert!(matches!(hard.difficulty(), Difficulty::Custom { .. }));
+    for _ in 0..4 {
+        rotated.rotate_90();
+        assert_consistent(&rotated);
+    }
+    assert_eq!(rotated.tile_states(), board.tile_states());
+    assert_eq!(rotated.bomb_positions(), board.bomb_positions());
+    assert_eq!(rotated.difficulty(), board.difficulty());
+}
+
+#[test]
+fn transforms_preserve_bomb_counts() {
+    let board = started_board();
+    let counts = bomb_counts(&board);
+    let transforms: [fn(&mut Board); 3] = [
+        Board::rotate_90,
+        Board::flip_horizontal,
+        Board::flip_vertical,
+    ];
+    for transform in transforms {
+        let mut transformed =
+            Board::from_fen(&board.to_fen().unwrap()).unwrap();
+        transform(&mut transformed);
+        assert_eq!(bomb_counts(&transformed), counts);
+        assert_consistent(&transformed);
+    }
+}
+
+#[test]
+fn flipping_twice_restores_board() {
+    let board = started_board();
+    let flips: [fn(&mut Board); 2] =
+        [Board::flip_horizontal, Board::flip_vertical];
+    for flip in flips {
+        let mut flipped = Board::from_fen(&board.to_fen().unwrap()).unwrap();
+        flip(&mut flipped);
+        assert_ne!(flipped.tile_states(), board.tile_states());
+        flip(&mut flipped);
+        assert_eq!(flipped.tile_states(), board.tile_states());
+        assert_eq!(flipped.bomb_positions(), board.bomb_positions());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn board_round_trips_through_json() {
+    let mut board = started_board();
+    let bomb = find_tile(&board, true);
+    board.apply_action(Action::flag(bomb));
+    let json = serde_json::to_string(&board).unwrap();
+    let loaded: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.tile_states(), board.tile_states());
+    assert_eq!(loaded.bomb_positions(), board.bomb_positions());
+    assert_eq!(loaded.difficulty(), board.difficulty());
+    assert_eq!(loaded.tile_state(bomb), TileState::Flagged);
+}
+
+#[test]
+fn opening_huge_empty_board_does_not_overflow() {
+    let bomb = TilePos { col: 99, row: 99 };
+    let mut board = Board::from_layout(100, 100, &[bomb]).unwrap();
+    assert_eq!(
+        board.apply_action(Action::uncover(TilePos { col: 0, row: 0 })),
+        ActionResult::Win
+    );
+    assert_eq!(
+        board.tile_state(TilePos { col: 98, row: 98 }),
+        TileState::UncoveredSafe(1)
+    );
+}
+
+#[test]
+fn initial_seed_deals_the_same_game_again() {
+    let mut board = Board::new(Difficulty::Hard, Some(5));
+    let bombs = board.bomb_positions();
+    // opening on a bomb moves the seed on
+    board.apply_action(Action::uncover(bombs[0]));
+    assert_ne!(board.seed(), board.initial_seed());
+    board.reset(Some(board.initial_seed()));
+    assert_eq!(board.bomb_positions(), bombs);
+}
+
+#[test]
+fn tiles_are_in_row_major_order() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    let tiles = board.tiles().collect::<Vec<_>>();
+    assert_eq!(tiles.len(), 25);
+    assert_eq!(tiles[1].0, TilePos { col: 1, row: 0 });
+    assert_eq!(tiles[5].0, TilePos { col: 0, row: 1 });
+    for (pos, state) in tiles {
+        assert_eq!(board.tile_state(pos), state);
+    }
+}
+
+#[test]
+fn strict_flags_lose_on_a_safe_tile() {
+    let flag_safe_tile = |strict_flags| {
+        let mut board = Board::parse_layout(LAYOUT)
+            .unwrap()
+            .with_strict_flags(strict_flags);
+        board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+        // a bomb is fine either way
+        let bomb = TilePos { col: 2, row: 2 };
+        assert_eq!(
+            board.apply_action(Action::flag(bomb)),
+            ActionResult::Continue
+        );
+        let safe = TilePos { col: 1, row: 4 };
+        (board.apply_action(Action::flag(safe)), board)
+    };
+    let (result, board) = flag_safe_tile(false);
+    assert_eq!(result, ActionResult::Continue);
+    assert_eq!(board.result(), ActionResult::Continue);
+    let (result, board) = flag_safe_tile(true);
+    assert_eq!(result, ActionResult::Lose);
+    assert_eq!(board.result(), ActionResult::Lose);
+    assert_eq!(
+        board.tile_state(TilePos { col: 1, row: 4 }),
+        TileState::Misflagged
+    );
+    assert_eq!(
+        board.tile_state(TilePos { col: 0, row: 0 }),
+        TileState::UncoveredBomb
+    );
+}
+
+#[test]
+fn win_condition_decides_whether_bombs_need_flags() {
+    let bombs = [(0, 0), (2, 2), (4, 4)].map(|(col, row)| TilePos { col, row });
+    let safe_tiles = |board: &Board| {
+        board
+            .tiles()
+            .map(|(pos, _)| pos)
+            .filter(|pos| !bombs.contains(pos))
+            .collect::<Vec<_>>()
+    };
+    let last_safe = TilePos { col: 1, row: 4 };
+
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    assert_eq!(board.win_condition(), WinCondition::ClearSafe);
+    let results = safe_tiles(&board)
+        .into_iter()
+        .map(|pos| board.apply_action(Action::uncover(pos)))
+        .collect::<Vec<_>>();
+    assert!(results.contains(&ActionResult::Win));
+    assert_eq!(board.result(), ActionResult::Win);
+    for pos in bombs {
+        assert_eq!(board.tile_state(pos), TileState::Flagged);
+    }
+
+    let mut board = Board::parse_layout(LAYOUT)
+        .unwrap()
+        .with_win_condition(WinCondition::FlagAllMines);
+    // flagged first so uncovering around it leaves it alone
+    board.apply_action(Action::flag(last_safe));
+    for pos in safe_tiles(&board) {
+        board.apply_action(Action::uncover(pos));
+    }
+    for pos in bombs {
+        assert_eq!(
+            board.apply_action(Action::flag(pos)),
+            ActionResult::Continue
+        );
+    }
+    // a flag on a safe tile holds the win back
+    assert_eq!(board.result(), ActionResult::Continue);
+    board.apply_action(Action::flag(last_safe));
+    assert_eq!(
+        board.apply_action(Action::uncover(last_safe)),
+        ActionResult::Win
+    );
+    assert_eq!(board.result(), ActionResult::Win);
+
+    // every safe tile uncovered is no longer enough
+    let mut board = Board::parse_layout(LAYOUT)
+        .unwrap()
+        .with_win_condition(WinCondition::FlagAllMines);
+    for pos in safe_tiles(&board) {
+        assert_eq!(
+            board.apply_action(Action::uncover(pos)),
+            ActionResult::Continue
+        );
+    }
+    assert_eq!(board.result(), ActionResult::Continue);
+    board.apply_action(Action::flag(bombs[0]));
+    board.apply_action(Action::flag(bombs[1]));
+    assert_eq!(
+        board.apply_action(Action::flag(bombs[2])),
+        ActionResult::Win
+    );
+}
+
+#[test]
+fn without_flags_only_takes_off_the_given_flags() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    let bomb = TilePos { col: 2, row: 2 };
+    let other = TilePos { col: 4, row: 4 };
+    board.apply_action(Action::flag(bomb));
+    board.apply_action(Action::flag(other));
+    let view = board.without_flags(&[bomb]);
+    assert_eq!(view.tile_state(bomb), TileState::Covered);
+    assert_eq!(view.tile_state(other), TileState::Flagged);
+    assert_eq!(view.num_bombs_left(), board.num_bombs_left() + 1);
+    assert_eq!(board.tile_state(bomb), TileState::Flagged);
+}
+
+#[test]
+fn board_displays_as_ascii() {
+    let mut board = Board::parse_layout(LAYOUT).unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 4, row: 0 }));
+    board.apply_action(Action::flag(TilePos { col: 2, row: 2 }));
+    board.apply_action(Action::flag(TilePos { col: 0, row: 4 }));
+    board.apply_action(Action::uncover(TilePos { col: 0, row: 0 }));
+    // the misflag no longer counts towards the bombs left
+    let expected = [
+        "5x5, 2 bombs left",
+        "X1   ",
+        ".211 ",
+        "..F1 ",
+        "...21",
+        "M...*",
+    ];
+    assert_eq!(board.to_string(), expected.join("\n"));
+}
+
+#[test]
+fn open_first_returns_opened_tiles() {
+    let mut board = Board::new(Difficulty::Hard, Some(5));
+    let first = board.bomb_positions()[0];
+    let opened = board.open_first(first);
+    assert!(opened.contains(&first));
+    let uncovered = board
+        .tiles()
+        .filter(|(_, state)| matches!(state, TileState::UncoveredSafe(_)))
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    assert_eq!(opened, uncovered);
+    // only the first click counts
+    assert!(board.open_first(TilePos { col: 0, row: 0 }).is_empty());
+}
+
+#[test]
+fn last_changes_lists_each_tile_the_action_changed() {
+    // positions must follow the width after a rotate on a non-square board
+    let mut board = Board::new(Difficulty::Hard, Some(5));
+    board.apply_action(Action::uncover(TilePos { col: 3, row: 7 }));
+    board.rotate_90();
+    let before = board.clone();
+    // a tile with no bombs around it opens its neighbours too
+    let pos = covered_tiles(&board)
+        .into_iter()
+        .find(|&pos| {
+            let mut board = board.clone();
+            board.apply_action(Action::uncover(pos));
+            board.tile_state(pos) == TileState::UncoveredSafe(0)
+        })
+        .unwrap();
+    board.apply_action(Action::uncover(pos));
+    let opened = board
+        .tiles()
+        .filter(|&(pos, state)| before.tile_state(pos) != state)
+        .map(|(pos, state)| (pos, TileState::Covered, state))
+        .collect::<Vec<_>>();
+    assert!(opened.len() > 1);
+    let mut changes = board.last_changes().to_vec();
+    changes.sort_by_key(|&(pos, _, _)| (pos.row, pos.col));
+    assert_eq!(changes, opened);
+    // only the latest action is kept
+    let pos = covered_tiles(&board)[0];
+    board.apply_action(Action::flag(pos));
+    assert_eq!(
+        board.last_changes(),
+        [(pos, TileState::Covered, TileState::Flagged)]
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn validate_names_the_broken_invariant() {
+    let board = started_board();
+    assert_eq!(board.validate(), Ok(()));
+    let tampered = |change: &dyn Fn(&mut serde_json::Value)| {
+        let mut json = serde_json::to_value(&board).unwrap();
+        change(&mut json);
+        serde_json::from_value::<Board>(json).unwrap().validate()
+    };
+    assert!(matches!(
+        tampered(&|json| json["bombs"].as_array_mut().unwrap().clear()),
+        Err(BoardError::WrongTileCount { num_bombs: 0, .. })
+    ));
+    assert!(matches!(
+        tampered(&|json| json["num_bombs_left"] = 1000.into()),
+        Err(BoardError::TooManyBombsLeft { .. })
+    ));
+    let safe = find_tile(&board, false);
+    let index = safe.row * board.width() + safe.col;
+    let shown = board.num_bombs_around(safe) + 1;
+    assert_eq!(
+        tampered(&|json| {
+            json["tile_states"][index] =
+                serde_json::json!({ "UncoveredSafe": shown })
+        }),
+        Err(BoardError::WrongNumber {
+            pos: safe,
+            shown,
+            actual: shown - 1,
+        })
+    );
+    let bomb = find_tile(&board, true);
+    let index = bomb.row * board.width() + bomb.col;
+    assert_eq!(
+        tampered(&|json| {
+            json["tile_states"][index] =
+                serde_json::json!({ "UncoveredSafe": 0 })
+        }),
+        Err(BoardError::InconsistentTile(bomb))
+    );
+}
+
+#[test]
+fn three_bv_counts_openings_and_lone_numbers() {
+    // one opening takes in every number but the one between the bombs
+    let board = Board::parse_layout(
+        "\
+*.*...
+......
+......
+......",
+    )
+    .unwrap();
+    assert_eq!(board.compute_3bv(), 2);
+    // no openings, every safe tile is its own click
+    let board = Board::parse_layout("*.*\n.*.").unwrap();
+    assert_eq!(board.compute_3bv(), 3);
+    // the middle bomb splits the openings in two
+    assert_eq!(Board::parse_layout(LAYOUT).unwrap().compute_3bv(), 2);
+}
+
+#[test]
+fn open_regions_are_split_by_bombs() {
+    let mut board = Board::parse_layout(
+        "\
+...*...
+...*...
+...*...",
+    )
+    .unwrap();
+    assert!(board.open_regions().is_empty());
+    board.apply_action(Action::uncover(TilePos { col: 0, row: 0 }));
+    board.apply_action(Action::uncover(TilePos { col: 6, row: 2 }));
+    let regions = board.open_regions();
+    assert_eq!(regions.len(), 2);
+    // the numbers beside the bombs close off each side
+    for (mut region, cols) in regions.into_iter().zip([0..3, 4..7]) {
+        let mut expected: Vec<_> = cols
+            .flat_map(|col| (0..3).map(move |row| TilePos { col, row }))
+            .collect();
+        region.sort();
+        expected.sort();
+        assert_eq!(region, expected);
+    }
+}
+
+#[test]
+fn crowded_boards_keep_one_safe_tile() {
+    assert_eq!(
+        Board::with_dimensions(3, 3, 9, None).err(),
+        Some(BoardError::TooManyBombs {
+            num_bombs: 9,
+            num_tiles: 9
+        })
+    );
+    let difficulty = Difficulty::Custom {
+        width: 3,
+        height: 3,
+        bombs: 20,
+    };
+    let mut board = Board::new(difficulty, Some(1));
+    assert_eq!(board.num_bombs_total(), 8);
+    // no room for a safe region, so only the clicked tile is kept safe
+    let centre = TilePos { col: 1, row: 1 };
+    assert_eq!(
+        board.apply_action(Action::uncover(centre)),
+        ActionResult::Win
+    );
+    let mut board = Board::with_dimensions(4, 4, 10, Some(1)).unwrap();
+    let result = board.apply_action(Action::uncover(centre));
+    assert_ne!(result, ActionResult::Lose);
+    assert!(matches!(
+        board.tile_state(centre),
+        TileState::UncoveredSafe(_)
+    ));
+}
+
+#[test]
+fn bombs_can_be_dealt_from_any_rng() {
+    // always drawing 0 puts the first bomb in the corner, then the rest at
+    // the end of the board
+    let board = Board::new_with_rng(Difficulty::Easy, StepRng::new(0, 0));
+    let mut expected = vec![TilePos { col: 0, row: 0 }];
+    expected.extend((1..10).map(|col| TilePos { col, row: 9 }));
+    let mut bombs = board.bomb_positions();
+    bombs.sort_by_key(|&TilePos { col, row }| (row, col));
+    assert_eq!(bombs, expected);
+    let rng = StdRng::seed_from_u64(5);
+    assert_eq!(
+        Board::new_with_rng(Difficulty::Hard, rng.clone()).bomb_positions(),
+        Board::new_with_rng(Difficulty::Hard, rng).bomb_positions()
+    );
+}
+
+#[test]
+fn chord_preview_needs_enough_flags() {
+    let mut board = Board::parse_layout("*..\n...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 2, row: 0 }));
+    let number = TilePos { col: 1, row: 0 };
+    assert_eq!(board.chord_preview(number), None);
+    board.apply_action(Action::flag(TilePos { col: 0, row: 0 }));
+    assert_eq!(
+        board.chord_preview(number),
+        Some(vec![TilePos { col: 0, row: 1 }])
+    );
+    // zeros have nothing left to uncover, and covered tiles aren't numbers
+    assert_eq!(
+        board.chord_preview(TilePos { col: 2, row: 0 }),
+        Some(vec![])
+    );
+    assert_eq!(board.chord_preview(TilePos { col: 0, row: 1 }), None);
+}
+
+#[test]
+fn simulated_actions_leave_the_board_alone() {
+    let board = Board::parse_layout("*...\n....").unwrap();
+    let (opened, result) =
+        board.simulate_action(&Action::uncover(TilePos { col: 3, row: 0 }));
+    assert_eq!(result, ActionResult::Continue);
+    // the zero opened everything up to the numbers next to the bomb
+    assert_eq!(opened.safe_tiles_remaining(), 1);
+    let (lost, result) =
+        opened.simulate_action(&Action::uncover(TilePos { col: 0, row: 0 }));
+    assert_eq!(result, ActionResult::Lose);
+    assert_eq!(
+        lost.tile_state(TilePos { col: 0, row: 0 }),
+        TileState::ExplodedBomb
+    );
+    assert!(board.tiles().all(|(_, state)| state == TileState::Covered));
+    assert_eq!(opened.result(), ActionResult::Continue);
+}
+
+#[test]
+fn set_flag_skips_the_question_mark_cycle() {
+    let mut board = started_board();
+    let bomb = find_tile(&board, true);
+    board.apply_action(Action::flag(bomb));
+    board.apply_action(Action::flag(bomb));
+    assert_eq!(board.tile_state(bomb), TileState::Questioned);
+    board.apply_action(Action::set_flag(bomb));
+    assert_eq!(board.tile_state(bomb), TileState::Flagged);
+    // already flagged, so nothing changes
+    board.apply_action(Action::set_flag(bomb));
+    assert_eq!(board.tile_state(bomb), TileState::Flagged);
+}
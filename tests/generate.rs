@@ -0,0 +1,52 @@
+#![cfg(feature = "serde")]
+
+use minesweeper::board::TileState;
+use minesweeper::{generate_boards, Difficulty, GeneratedBoard};
+
+fn generated(seed: u64, solved: bool) -> Vec<GeneratedBoard> {
+    let path = std::env::temp_dir()
+        .join(format!("minesweeper_generate_test_{seed}_{solved}.jsonl"));
+    generate_boards(4, Difficulty::Medium, seed, solved, &path).unwrap();
+    let boards = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    std::fs::remove_file(&path).unwrap();
+    boards
+}
+
+#[test]
+fn generated_boards_are_reproducible() {
+    let boards = generated(3, false);
+    assert_eq!(boards.len(), 4);
+    assert_eq!(boards, generated(3, false));
+    assert_ne!(boards, generated(4, false));
+    for generated in &boards {
+        assert!(generated.tile_states.is_none());
+        let board = generated.board().unwrap();
+        assert_eq!(board.bomb_positions(), generated.bombs);
+    }
+}
+
+#[test]
+fn solved_boards_include_finished_tiles() {
+    for generated in generated(5, true) {
+        let board = generated.board().unwrap();
+        let tile_states = generated.tile_states.clone().unwrap();
+        assert_eq!(tile_states.len(), generated.width * generated.height);
+        let tiles = board.tiles().map(|(pos, _)| pos).zip(tile_states);
+        let mut lost = false;
+        let mut won = true;
+        for (pos, state) in tiles {
+            match state {
+                TileState::UncoveredSafe(n) => {
+                    assert_eq!(n, board.num_bombs_around(pos));
+                }
+                TileState::ExplodedBomb | TileState::Misflagged => lost = true,
+                _ => won &= generated.bombs.contains(&pos),
+            }
+        }
+        assert!(won || lost);
+    }
+}
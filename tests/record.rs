@@ -0,0 +1,62 @@
+use minesweeper::agent;
+use minesweeper::board::{ActionResult, Board};
+use minesweeper::{Difficulty, Record, Stats};
+
+/// Plays out the outcomes through `Stats`, the way finished games are counted
+fn played(
+    difficulty: Difficulty,
+    win: usize,
+    loss: usize,
+    dnf: usize,
+) -> Record {
+    let board = Board::new(difficulty, Some(0));
+    let mut stats = Stats::default();
+    let outcomes = [
+        (ActionResult::Win, win),
+        (ActionResult::Lose, loss),
+        (ActionResult::Continue, dnf),
+    ];
+    for (result, count) in outcomes {
+        for _ in 0..count {
+            stats.add_game(&result, &board);
+        }
+    }
+    stats.record(difficulty).cloned().unwrap_or_default()
+}
+
+fn assert_interval(record: Record, z: f64, expected: (f64, f64)) {
+    let (low, high) = record.win_rate_interval(z);
+    assert!((low - expected.0).abs() < 1e-9, "{low} != {}", expected.0);
+    assert!((high - expected.1).abs() < 1e-9, "{high} != {}", expected.1);
+}
+
+#[test]
+fn win_rate_interval_matches_known_values() {
+    let record = played(Difficulty::Hard, 81, 182, 0);
+    assert_interval(record, 1.96, (0.2552876130636696, 0.36621068405342155));
+    let record = played(Difficulty::Easy, 50, 40, 10);
+    assert_interval(record, 1.0, (0.45024814048950057, 0.5497518595104994));
+}
+
+#[test]
+fn win_rate_interval_stays_within_bounds() {
+    let record = played(Difficulty::Easy, 10, 0, 0);
+    assert_interval(record, 1.96, (0.7224598312333834, 1.0));
+    let record = played(Difficulty::Easy, 0, 7, 3);
+    assert_interval(record, 1.96, (0.0, 0.2775401687666166));
+    let record = played(Difficulty::Easy, 0, 0, 0);
+    assert_interval(record, 1.96, (0.0, 1.0));
+}
+
+#[test]
+fn undone_game_comes_off_the_record() {
+    let mut board = Board::new(Difficulty::Easy, Some(3));
+    let result = agent::solve(&mut board);
+    let mut stats = Stats::default();
+    stats.add_game(&ActionResult::Win, &Board::new(Difficulty::Easy, Some(0)));
+    let before = stats.record(Difficulty::Easy).cloned();
+    stats.add_game(&result, &board);
+    assert_ne!(stats.record(Difficulty::Easy).cloned(), before);
+    stats.remove_game(&result, &board);
+    assert_eq!(stats.record(Difficulty::Easy).cloned(), before);
+}
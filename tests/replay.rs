@@ -0,0 +1,105 @@
+use minesweeper::agent;
+use minesweeper::board::{
+    Action, ActionResult, Adjacency, Board, FirstClickPolicy, TilePos, Topology,
+};
+use minesweeper::replay::Replay;
+use minesweeper::Difficulty;
+
+fn recorded_game(seed: u64) -> (Board, Replay) {
+    record(Board::new(Difficulty::Medium, Some(seed)))
+}
+
+fn record(mut board: Board) -> (Board, Replay) {
+    let mut replay = Replay::default();
+    // a flag before the first uncover, which then moves the seed on
+    let corner = TilePos { col: 0, row: 0 };
+    replay.apply(&mut board, Action::flag(corner));
+    replay.apply(&mut board, Action::flag(corner));
+    'game: loop {
+        for (action, _) in agent::get_all_actions(&board) {
+            if replay.apply(&mut board, action) != ActionResult::Continue {
+                break 'game;
+            }
+        }
+    }
+    (board, replay)
+}
+
+#[test]
+fn replay_reproduces_final_tiles() {
+    for seed in 0..5 {
+        let (board, replay) = recorded_game(seed);
+        assert_eq!(replay.play().tile_states(), board.tile_states());
+    }
+}
+
+#[test]
+fn replay_keeps_the_board_settings() {
+    for policy in [FirstClickPolicy::SafeCell, FirstClickPolicy::Unsafe] {
+        for seed in 0..5 {
+            let board = Board::new(Difficulty::Medium, Some(seed))
+                .with_first_click_policy(policy)
+                .with_topology(Topology::Toroidal)
+                .with_adjacency(Adjacency::VonNeumann)
+                .with_strict_flags(true);
+            let (board, replay) = record(board);
+            let replayed = replay.play();
+            assert_eq!(replayed.first_click_policy(), policy);
+            assert_eq!(replayed.tile_states(), board.tile_states());
+        }
+    }
+}
+
+#[test]
+fn replay_follows_undo_and_redo() {
+    let mut board = Board::new(Difficulty::Easy, Some(7));
+    let mut replay = Replay::default();
+    let first = TilePos { col: 4, row: 4 };
+    replay.apply(&mut board, Action::uncover(first));
+    let next = agent::next_action(&board).unwrap();
+    replay.apply(&mut board, next);
+    // uncovering the same tile again changes nothing, so isn't recorded
+    replay.apply(&mut board, Action::uncover(first));
+    assert_eq!(replay.actions().len(), 2);
+    let before = board.tile_states().clone();
+    assert!(replay.undo(&mut board));
+    assert_eq!(replay.play().tile_states(), board.tile_states());
+    assert!(replay.redo(&mut board));
+    assert_eq!(replay.play().tile_states(), board.tile_states());
+    assert_eq!(*board.tile_states(), before);
+    // nothing to redo once the last undone move is made again
+    assert!(!replay.redo(&mut board));
+    assert!(replay.undo(&mut board) && replay.undo(&mut board));
+    assert!(!replay.undo(&mut board));
+    assert!(board.tile_states().iter().all(|state| state.covered()));
+}
+
+#[test]
+fn undo_only_goes_back_so_far() {
+    let mut board = Board::new(Difficulty::Easy, Some(7));
+    let mut replay = Replay::default();
+    // each flag moves the tile on to the next state, so every one is a move
+    let corner = TilePos { col: 0, row: 0 };
+    for _ in 0..Replay::MAX_UNDO + 5 {
+        replay.apply(&mut board, Action::flag(corner));
+    }
+    let mut num_undone = 0;
+    while replay.undo(&mut board) {
+        num_undone += 1;
+    }
+    assert_eq!(num_undone, Replay::MAX_UNDO);
+    // the moves that can't be undone are still part of the game
+    assert_eq!(replay.actions().len(), 5);
+    assert_eq!(replay.play().tile_states(), board.tile_states());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn replay_round_trips_through_file() {
+    let (_, replay) = recorded_game(11);
+    let path = std::env::temp_dir().join("minesweeper_replay_test.json");
+    replay.save(&path).unwrap();
+    let loaded = Replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, replay);
+}
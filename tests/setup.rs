@@ -0,0 +1,166 @@
+use bevy::prelude::{Input, KeyCode, Vec2, Window};
+use minesweeper::board::{Action, Board, TilePos};
+use minesweeper::setup::{self, Theme, ThemeError, UISizing};
+use minesweeper::{CameraView, HintFlash};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn clicks_hit_the_same_tile_at_any_height() {
+    for window_height in [300.0, 700.0, 1400.0] {
+        let ui_sizing = UISizing::new_with_height((30, 16), window_height);
+        assert_eq!(ui_sizing.window_size.1, window_height);
+        for pos in [
+            TilePos { col: 0, row: 0 },
+            TilePos { col: 29, row: 0 },
+            TilePos { col: 12, row: 7 },
+            TilePos { col: 29, row: 15 },
+        ] {
+            // centre of the tile, in window coordinates from the top left
+            let click = Vec2::new(
+                ui_sizing.edge_padding
+                    + (pos.col as f32 + 0.5) * ui_sizing.tile_size,
+                ui_sizing.top_padding
+                    + (pos.row as f32 + 0.5) * ui_sizing.tile_size,
+            );
+            assert_eq!(ui_sizing.clicked_tile_pos(click), Some(pos));
+        }
+        // the padding below the board misses
+        let below = Vec2::new(
+            ui_sizing.window_size.0 / 2.0,
+            ui_sizing.top_padding + ui_sizing.board_size.1 + 1.0,
+        );
+        assert_eq!(ui_sizing.clicked_tile_pos(below), None);
+    }
+}
+
+#[test]
+fn resizing_ignores_a_minimised_window() {
+    let ui_sizing = UISizing::new((30, 16));
+    // minimising on Windows reports a height of 0
+    assert!(ui_sizing.resized(0.0).is_none());
+    assert!(ui_sizing
+        .resized(UISizing::MIN_WINDOW_HEIGHT - 1.0)
+        .is_none());
+    let resized = ui_sizing.resized(UISizing::MIN_WINDOW_HEIGHT).unwrap();
+    assert_eq!(resized.grid_size, ui_sizing.grid_size);
+    assert_eq!(resized.window_size.1, UISizing::MIN_WINDOW_HEIGHT);
+    assert!(resized.tile_size < ui_sizing.tile_size);
+}
+
+#[test]
+fn zooming_keeps_the_tile_under_the_cursor() {
+    let ui_sizing = UISizing::new((30, 16));
+    let (width, height) = ui_sizing.window_size;
+    let mut window = Window::default();
+    window.resolution.set(width, height);
+    let cursor = Vec2::new(width * 0.3, height * 0.6);
+    window.set_cursor_position(Some(cursor));
+    let mut view = CameraView::default();
+    let hit = |view: &CameraView| {
+        ui_sizing.clicked_tile_pos(view.cursor_position(&window).unwrap())
+    };
+    let before = hit(&view);
+    assert!(before.is_some());
+    let offset =
+        (cursor - Vec2::new(width, height) / 2.0) * Vec2::new(1.0, -1.0);
+    view.zoom(0.5, offset, Vec2::new(width, height));
+    assert_eq!(hit(&view), before);
+    // the middle of the window has moved towards where the cursor zoomed in
+    let centre = Vec2::new(width, height) / 2.0;
+    window.set_cursor_position(Some(centre));
+    let moved = view.cursor_position(&window).unwrap();
+    assert!((moved - centre).length() < (cursor - centre).length());
+    // can't zoom out past the whole window
+    view.zoom(100.0, offset, Vec2::new(width, height));
+    assert_eq!(view, CameraView::default());
+}
+
+// a copy of the default assets, absolute so it's used as is
+#[test]
+fn hint_needs_a_question_mark() {
+    let mut keys = Input::<KeyCode>::default();
+    keys.press(KeyCode::Slash);
+    assert!(!HintFlash::requested(&keys));
+    // shift held from before, as it is when typing ?
+    let mut keys = Input::<KeyCode>::default();
+    keys.press(KeyCode::ShiftRight);
+    keys.clear();
+    keys.press(KeyCode::Slash);
+    assert!(HintFlash::requested(&keys));
+}
+
+fn copy_default_assets(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+    for folder in ["spritesheets", "padding", "sounds"] {
+        std::fs::create_dir_all(dir.join(folder)).unwrap();
+        for entry in std::fs::read_dir(assets.join(folder)).unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(
+                &path,
+                dir.join(folder).join(path.file_name().unwrap()),
+            )
+            .unwrap();
+        }
+    }
+    dir
+}
+
+#[test]
+fn themes_need_sheets_the_size_of_the_defaults() {
+    assert_eq!(Theme::new(PathBuf::new()).unwrap(), Theme::default());
+    let dir = copy_default_assets("minesweeper_theme_test");
+    assert!(Theme::new(dir.clone()).is_ok());
+    let tiles = dir.join("spritesheets/minesweeper_tiles.png");
+    image::RgbaImage::new(32, 32).save(&tiles).unwrap();
+    let error = Theme::new(dir.clone()).unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(
+        error,
+        ThemeError::WrongSize {
+            expected: (64, 64),
+            found: (32, 32),
+            ..
+        }
+    ));
+    assert!(matches!(Theme::new(dir), Err(ThemeError::Unreadable(_))));
+}
+
+#[test]
+fn rendered_board_has_a_sprite_per_tile() {
+    let mut board = Board::parse_layout("*..\n...\n...").unwrap();
+    board.apply_action(Action::uncover(TilePos { col: 1, row: 1 }));
+    board.apply_action(Action::flag(TilePos { col: 2, row: 2 }));
+    let ui_sizing = UISizing::new((board.width(), board.height()));
+    let path = std::env::temp_dir().join("minesweeper_render_test.png");
+    setup::render_png(&board, &path, &Theme::default(), &ui_sizing).unwrap();
+    let image = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_file(&path).unwrap();
+    let size = ui_sizing.tile_size.round() as u32;
+    assert_eq!(image.dimensions(), (3 * size, 3 * size));
+    let tile = |col: u32, row: u32| {
+        image::imageops::crop_imm(&image, col * size, row * size, size, size)
+            .to_image()
+    };
+    // covered tiles look alike, unlike the number and the flag
+    assert_eq!(tile(0, 0), tile(1, 0));
+    assert_ne!(tile(0, 0), tile(1, 1));
+    assert_ne!(tile(0, 0), tile(2, 2));
+}
+
+#[test]
+fn rendered_board_uses_the_theme_tiles() {
+    let dir = copy_default_assets("minesweeper_render_theme_test");
+    let red = image::Rgba([255, 0, 0, 255]);
+    image::RgbaImage::from_pixel(64, 64, red)
+        .save(dir.join("spritesheets/minesweeper_tiles.png"))
+        .unwrap();
+    let theme = Theme::new(dir.clone()).unwrap();
+    let board = Board::parse_layout("*..\n...").unwrap();
+    let ui_sizing = UISizing::new((board.width(), board.height()));
+    let path = dir.join("render.png");
+    setup::render_png(&board, &path, &theme, &ui_sizing).unwrap();
+    let image = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(image.pixels().all(|&pixel| pixel == red));
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "serde")]
+
+use minesweeper::agent;
+use minesweeper::board::Board;
+use minesweeper::{Difficulty, Stats};
+
+#[test]
+fn stats_round_trip_through_file() {
+    let mut stats = Stats::default();
+    for seed in 0..3 {
+        let mut board = Board::new(Difficulty::Easy, Some(seed));
+        let result = agent::solve(&mut board);
+        stats.add_game(&result, &board);
+    }
+    // per process, so parallel runs of the suite don't share a file
+    let name = format!("minesweeper_stats_test_{}.json", std::process::id());
+    let path = std::env::temp_dir().join(name);
+    stats.save(&path).unwrap();
+    let loaded = Stats::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        loaded.record(Difficulty::Easy),
+        stats.record(Difficulty::Easy)
+    );
+    assert_eq!(loaded.record(Difficulty::Hard), None);
+}
@@ -0,0 +1,52 @@
+#![cfg(not(target_family = "wasm"))]
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use minesweeper::board::{ActionResult, FirstClickPolicy, TilePos};
+use minesweeper::tui::{Game, Key};
+use minesweeper::Difficulty;
+
+#[test]
+fn keys_come_from_arrows_and_letters() {
+    let keys = [
+        KeyEvent::from(KeyCode::Up),
+        KeyEvent::from(KeyCode::Right),
+        KeyEvent::from(KeyCode::Enter),
+        KeyEvent::from(KeyCode::Char('f')),
+        KeyEvent::from(KeyCode::Char('b')),
+        KeyEvent::from(KeyCode::Char('q')),
+        KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        KeyEvent::from(KeyCode::Char('c')),
+    ]
+    .map(Key::from_event);
+    assert_eq!(
+        keys,
+        [
+            Key::Step(0, -1),
+            Key::Step(1, 0),
+            Key::Uncover,
+            Key::Flag,
+            Key::Bot,
+            Key::Quit,
+            Key::Quit,
+            Key::Other,
+        ]
+    );
+}
+
+#[test]
+fn bot_moves_play_a_game_out() {
+    let mut game = Game::new(Difficulty::Easy, FirstClickPolicy::default());
+    // the cursor stays on the board
+    for _ in 0..20 {
+        game.press(Key::Step(-1, -1));
+    }
+    assert_eq!(game.cursor, TilePos { col: 0, row: 0 });
+    while game.result == ActionResult::Continue {
+        game.press(Key::Bot);
+    }
+    assert!(game.render().contains(match game.result {
+        ActionResult::Win => "You won!",
+        _ => "You lost",
+    }));
+    assert!(!game.press(Key::Quit));
+}